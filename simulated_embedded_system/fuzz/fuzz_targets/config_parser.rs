@@ -0,0 +1,22 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use simulated_embedded_system::config::{AlertConfig, Config, TemperatureUnit};
+use simulated_embedded_system::history::RingBuffer;
+
+// Config and thresholds parsing must reject bad input with an error, never panic
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    if let Ok(config) = Config::parse(input) {
+        // Build what the config sizes directly, e.g. a huge `buffer_size`
+        if let Some(server) = &config.server {
+            let _ = RingBuffer::new(server.buffer_size);
+        }
+    }
+    for unit in [TemperatureUnit::Celsius, TemperatureUnit::Fahrenheit] {
+        let _ = AlertConfig::parse(input, unit);
+    }
+});
@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use simulated_embedded_system::config::NanRepresentation;
+use simulated_embedded_system::logger::parse_log_line;
+
+// Every log line, however malformed, parses to `Some` or `None` without panicking
+fuzz_target!(|data: &[u8]| {
+    if let Ok(line) = std::str::from_utf8(data) {
+        for nan in [
+            NanRepresentation::Nan,
+            NanRepresentation::Empty,
+            NanRepresentation::Sentinel(-999.0),
+        ] {
+            let _ = parse_log_line(line, nan);
+        }
+    }
+});
@@ -0,0 +1,580 @@
+use crate::bus::Channel;
+use crate::config::{AlertConfig, TemperatureUnit};
+use crate::sensor::Reading;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Tracks since when an alert condition has held continuously
+#[derive(Default)]
+pub struct ConditionTimer {
+    since: Option<Instant>,
+    // Wall-clock time of `since`, for saving the state
+    since_wall: Option<SystemTime>,
+    // Whether the condition has fired since it became active
+    fired: bool,
+    // Whether it had already fired before the state was restored
+    fired_before_restart: bool,
+    // When its last alert was raised
+    last_alert: Option<Instant>,
+}
+
+impl ConditionTimer {
+    // Whether the condition is active and has been for at least `min_duration`.
+    // The timer resets as soon as the condition clears.
+    pub fn holds(&mut self, active: bool, min_duration: Duration, now: Instant) -> bool {
+        if !active {
+            *self = ConditionTimer::default();
+            return false;
+        }
+        let since = *self.since.get_or_insert(now);
+        self.since_wall.get_or_insert_with(SystemTime::now);
+        let holds = now.duration_since(since) >= min_duration;
+        self.fired |= holds;
+        holds
+    }
+
+    // Whether the condition holds and hasn't alerted within `cooldown`. Once
+    // it clears, the next crossing alerts right away.
+    fn fires(
+        &mut self,
+        active: bool,
+        min_duration: Duration,
+        cooldown: Duration,
+        now: Instant,
+    ) -> bool {
+        if !self.holds(active, min_duration, now) {
+            return false;
+        }
+        if self
+            .last_alert
+            .is_some_and(|last_alert| now.duration_since(last_alert) < cooldown)
+        {
+            return false;
+        }
+        self.last_alert = Some(now);
+        true
+    }
+
+    fn save(&self) -> Option<SavedCondition> {
+        let since = self.since_wall?.duration_since(UNIX_EPOCH).ok()?;
+        Some(SavedCondition {
+            since_ms: since.as_millis() as u64,
+            fired: self.fired,
+        })
+    }
+
+    // The timer as saved, keeping the time the condition has already held
+    fn restore(saved: &SavedCondition, now: Instant) -> Self {
+        let since_wall = UNIX_EPOCH + Duration::from_millis(saved.since_ms);
+        let held = SystemTime::now()
+            .duration_since(since_wall)
+            .unwrap_or_default();
+        ConditionTimer {
+            since: Some(now.checked_sub(held).unwrap_or(now)),
+            since_wall: Some(since_wall),
+            fired: saved.fired,
+            fired_before_restart: saved.fired,
+            last_alert: None,
+        }
+    }
+}
+
+// Debounce and cooldown state of each sensor's alert conditions
+#[derive(Default)]
+pub struct AlertManager {
+    temperature: ConditionTimer,
+    humidity: ConditionTimer,
+    light: ConditionTimer,
+    pressure: ConditionTimer,
+    co2: ConditionTimer,
+    temperature_low: ConditionTimer,
+    humidity_low: ConditionTimer,
+    light_low: ConditionTimer,
+    temperature_rate: ConditionTimer,
+    // Unit of the temperatures checked, for the alert messages
+    temperature_unit: TemperatureUnit,
+    // Last temperature that differed from the one before it, and when it was
+    // read, to measure the rate of change from
+    previous_temperature: Option<(f32, Instant)>,
+}
+
+impl AlertManager {
+    pub fn new(temperature_unit: TemperatureUnit) -> Self {
+        AlertManager {
+            temperature_unit,
+            ..AlertManager::default()
+        }
+    }
+
+    fn timer(&self, sensor: Channel, kind: AlertKind) -> &ConditionTimer {
+        match (sensor, kind) {
+            (Channel::Temperature, AlertKind::High) => &self.temperature,
+            (Channel::Humidity, AlertKind::High) => &self.humidity,
+            (Channel::Light, AlertKind::High) => &self.light,
+            (Channel::Pressure, _) => &self.pressure,
+            (Channel::Co2, _) => &self.co2,
+            (Channel::Temperature, AlertKind::Low) => &self.temperature_low,
+            (Channel::Humidity, AlertKind::Low) => &self.humidity_low,
+            (Channel::Light, AlertKind::Low) => &self.light_low,
+            // Only the temperature has a rate-of-change rule
+            (_, AlertKind::Rate) => &self.temperature_rate,
+        }
+    }
+
+    // Whether the condition of `alert` was already alerting before the state
+    // was restored and hasn't cleared since, so its alerts were already paged
+    pub fn fired_before_restart(&self, alert: &Alert) -> bool {
+        self.timer(alert.sensor, alert.kind).fired_before_restart
+    }
+
+    fn save(&self) -> SavedDebounce {
+        SavedDebounce {
+            temperature: self.temperature.save(),
+            humidity: self.humidity.save(),
+            light: self.light.save(),
+            pressure: self.pressure.save(),
+            co2: self.co2.save(),
+            temperature_low: self.temperature_low.save(),
+            humidity_low: self.humidity_low.save(),
+            light_low: self.light_low.save(),
+            temperature_rate: self.temperature_rate.save(),
+        }
+    }
+
+    fn restore(saved: &SavedDebounce, temperature_unit: TemperatureUnit, now: Instant) -> Self {
+        let restore = |saved: &Option<SavedCondition>| {
+            saved
+                .as_ref()
+                .map(|saved| ConditionTimer::restore(saved, now))
+                .unwrap_or_default()
+        };
+        AlertManager {
+            temperature: restore(&saved.temperature),
+            humidity: restore(&saved.humidity),
+            light: restore(&saved.light),
+            pressure: restore(&saved.pressure),
+            co2: restore(&saved.co2),
+            temperature_low: restore(&saved.temperature_low),
+            humidity_low: restore(&saved.humidity_low),
+            light_low: restore(&saved.light_low),
+            temperature_rate: restore(&saved.temperature_rate),
+            temperature_unit,
+            previous_temperature: None,
+        }
+    }
+}
+
+// An active alert condition as kept in the state file
+#[derive(Serialize, Deserialize)]
+struct SavedCondition {
+    // When the condition became active, in milliseconds since the Unix epoch
+    since_ms: u64,
+    fired: bool,
+}
+
+// Only the active conditions are saved
+#[derive(Default, Serialize, Deserialize)]
+struct SavedDebounce {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    temperature: Option<SavedCondition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    humidity: Option<SavedCondition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    light: Option<SavedCondition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pressure: Option<SavedCondition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    co2: Option<SavedCondition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    temperature_low: Option<SavedCondition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    humidity_low: Option<SavedCondition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    light_low: Option<SavedCondition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    temperature_rate: Option<SavedCondition>,
+}
+
+// Keeps the alert conditions in a JSON file, so that conditions carry on
+// across restarts instead of starting over. The file is replaced as a whole
+// and only written when the state changed.
+pub struct AlertStateFile {
+    path: String,
+    saved: String,
+}
+
+impl AlertStateFile {
+    pub fn new(path: &str) -> Self {
+        AlertStateFile {
+            path: path.to_string(),
+            saved: String::new(),
+        }
+    }
+
+    // The saved state, or a fresh one if there is no file yet
+    pub fn load(
+        &mut self,
+        temperature_unit: TemperatureUnit,
+        now: Instant,
+    ) -> Result<AlertManager, Box<dyn Error>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(AlertManager::new(temperature_unit))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let saved: SavedDebounce = serde_json::from_str(&contents)?;
+        self.saved = contents;
+        Ok(AlertManager::restore(&saved, temperature_unit, now))
+    }
+
+    pub fn save(&mut self, manager: &AlertManager) -> Result<(), Box<dyn Error>> {
+        let contents = serde_json::to_string(&manager.save())?;
+        if contents == self.saved {
+            return Ok(());
+        }
+        let temp_path = format!("{}.tmp", self.path);
+        fs::write(&temp_path, &contents)?;
+        fs::rename(&temp_path, &self.path)?;
+        self.saved = contents;
+        Ok(())
+    }
+}
+
+// An alert fired by one sensor
+pub struct Alert {
+    pub sensor: Channel,
+    pub kind: AlertKind,
+    // Reading that raised the alert
+    pub value: f32,
+    pub message: String,
+}
+
+// Which of a sensor's conditions an alert is about
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertKind {
+    // Above the threshold
+    High,
+    // Below the minimum
+    Low,
+    // Changing faster than the rate threshold
+    Rate,
+}
+
+impl fmt::Display for AlertKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AlertKind::High => write!(f, "high"),
+            AlertKind::Low => write!(f, "low"),
+            AlertKind::Rate => write!(f, "rate"),
+        }
+    }
+}
+
+impl AlertManager {
+    // Alerts due for `reading`: conditions that have held for their minimum
+    // duration and haven't alerted within the cooldown
+    pub fn check(&mut self, reading: &Reading, config: &AlertConfig, now: Instant) -> Vec<Alert> {
+        let cooldown = Duration::from_secs(config.cooldown_seconds);
+        let Reading {
+            temperature,
+            humidity,
+            light,
+            ..
+        } = *reading;
+        let mut alerts = Vec::new();
+        if self.temperature.fires(
+            temperature > config.temperature_threshold,
+            Duration::from_secs(config.temperature_alert_min_duration_secs),
+            cooldown,
+            now,
+        ) {
+            alerts.push(Alert {
+                sensor: Channel::Temperature,
+                kind: AlertKind::High,
+                value: temperature,
+                message: format!(
+                    "Temperature exceeded threshold: {:.2}{}",
+                    temperature,
+                    self.temperature_unit.symbol()
+                ),
+            });
+        }
+        if self.humidity.fires(
+            humidity > config.humidity_threshold,
+            Duration::from_secs(config.humidity_alert_min_duration_secs),
+            cooldown,
+            now,
+        ) {
+            alerts.push(Alert {
+                sensor: Channel::Humidity,
+                kind: AlertKind::High,
+                value: humidity,
+                message: format!("Humidity exceeded threshold: {:.2}%", humidity),
+            });
+        }
+        if self.light.fires(
+            light > config.light_threshold,
+            Duration::from_secs(config.light_alert_min_duration_secs),
+            cooldown,
+            now,
+        ) {
+            alerts.push(Alert {
+                sensor: Channel::Light,
+                kind: AlertKind::High,
+                value: light,
+                message: format!("Light intensity exceeded threshold: {:.2}%", light),
+            });
+        }
+        // Under-range alerts of the sensors with a minimum
+        for (timer, sensor, name, value, unit, min, min_duration_secs) in [
+            (
+                &mut self.temperature_low,
+                Channel::Temperature,
+                "Temperature",
+                temperature,
+                self.temperature_unit.symbol(),
+                config.temperature_min,
+                config.temperature_alert_min_duration_secs,
+            ),
+            (
+                &mut self.humidity_low,
+                Channel::Humidity,
+                "Humidity",
+                humidity,
+                "%",
+                config.humidity_min,
+                config.humidity_alert_min_duration_secs,
+            ),
+            (
+                &mut self.light_low,
+                Channel::Light,
+                "Light intensity",
+                light,
+                "%",
+                config.light_min,
+                config.light_alert_min_duration_secs,
+            ),
+        ] {
+            if timer.fires(
+                min.is_some_and(|min| value < min),
+                Duration::from_secs(min_duration_secs),
+                cooldown,
+                now,
+            ) {
+                alerts.push(Alert {
+                    sensor,
+                    kind: AlertKind::Low,
+                    value,
+                    message: format!("{} below minimum: {:.2}{}", name, value, unit),
+                });
+            }
+        }
+        // Without a change since the previous reading, a rapid change is over
+        let rate = self.temperature_rate(temperature, now);
+        let rapid = rate.zip(config.temperature_rate_threshold);
+        if self.temperature_rate.fires(
+            rapid.is_some_and(|(rate, threshold)| rate.abs() > threshold),
+            Duration::ZERO,
+            cooldown,
+            now,
+        ) {
+            if let Some((rate, _)) = rapid {
+                let direction = if rate < 0.0 { "falling" } else { "rising" };
+                alerts.push(Alert {
+                    sensor: Channel::Temperature,
+                    kind: AlertKind::Rate,
+                    value: temperature,
+                    message: format!(
+                        "Temperature {} rapidly: {:.1}{}/s",
+                        direction,
+                        rate.abs(),
+                        self.temperature_unit.symbol()
+                    ),
+                });
+            }
+        }
+        // The optional sensors alert only when both the sensor and its
+        // threshold are configured
+        let pressure = reading.pressure.zip(config.pressure_threshold);
+        if self.pressure.fires(
+            pressure.is_some_and(|(pressure, threshold)| pressure > threshold),
+            Duration::ZERO,
+            cooldown,
+            now,
+        ) {
+            if let Some((pressure, _)) = pressure {
+                alerts.push(Alert {
+                    sensor: Channel::Pressure,
+                    kind: AlertKind::High,
+                    value: pressure,
+                    message: format!("Pressure exceeded threshold: {:.2} hPa", pressure),
+                });
+            }
+        }
+        let co2 = reading.co2.zip(config.co2_threshold);
+        if self.co2.fires(
+            co2.is_some_and(|(co2, threshold)| co2 > threshold),
+            Duration::ZERO,
+            cooldown,
+            now,
+        ) {
+            if let Some((co2, _)) = co2 {
+                alerts.push(Alert {
+                    sensor: Channel::Co2,
+                    kind: AlertKind::High,
+                    value: co2,
+                    message: format!("CO2 exceeded threshold: {:.0} ppm", co2),
+                });
+            }
+        }
+        alerts
+    }
+
+    // Temperature change per second since the previous reading, or `None` for
+    // the first one. Between its samples a sensor's last reading is repeated,
+    // so the rate is measured from the last reading that differed; repeats
+    // don't count as a standstill. Failed (NaN) readings are skipped.
+    fn temperature_rate(&mut self, temperature: f32, now: Instant) -> Option<f32> {
+        if !temperature.is_finite() {
+            return None;
+        }
+        let previous = self.previous_temperature;
+        if previous.is_some_and(|(value, _)| value == temperature) {
+            return None;
+        }
+        self.previous_temperature = Some((temperature, now));
+        let (value, at) = previous?;
+        let elapsed = now.duration_since(at).as_secs_f32();
+        (elapsed > 0.0).then(|| (temperature - value) / elapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(temperature: f32) -> Reading {
+        Reading {
+            temperature,
+            humidity: 40.0,
+            light: 50.0,
+            pressure: None,
+            co2: None,
+        }
+    }
+
+    // Seconds into a stream of one reading per second at which alerts fire
+    fn alert_times(config: &str, temperatures: &[f32]) -> Vec<usize> {
+        let config = AlertConfig::parse(config, TemperatureUnit::Celsius).unwrap();
+        let mut manager = AlertManager::default();
+        let start = Instant::now();
+        temperatures
+            .iter()
+            .enumerate()
+            .filter(|&(second, &temperature)| {
+                let now = start + Duration::from_secs(second as u64);
+                !manager
+                    .check(&reading(temperature), &config, now)
+                    .is_empty()
+            })
+            .map(|(second, _)| second)
+            .collect()
+    }
+
+    const THRESHOLDS: &str = "
+        temperature_threshold = 25.0
+        humidity_threshold = 60.0
+        light_threshold = 80.0
+    ";
+
+    #[test]
+    fn cooldown_suppresses_repeats() {
+        let config = format!("{}cooldown_seconds = 3", THRESHOLDS);
+        let sustained = [26.0; 8];
+        assert_eq!(alert_times(&config, &sustained), [0, 3, 6]);
+        // Without a cooldown every reading above the threshold alerts
+        assert_eq!(alert_times(THRESHOLDS, &sustained[..3]), [0, 1, 2]);
+    }
+
+    #[test]
+    fn crossing_again_alerts_within_the_cooldown() {
+        let config = format!("{}cooldown_seconds = 60", THRESHOLDS);
+        let temperatures = [26.0, 27.0, 24.0, 26.0, 26.5, 20.0, 30.0];
+        assert_eq!(alert_times(&config, &temperatures), [0, 3, 6]);
+    }
+
+    #[test]
+    fn rapid_temperature_changes_alert() {
+        let config = format!("{}temperature_rate_threshold = 2.0", THRESHOLDS);
+        // The first reading has nothing to compare with, and the repeated 24.0
+        // is measured from its first read: 1°C over 2 seconds
+        let temperatures = [20.0, 20.5, 24.0, 24.0, 25.0, 21.0];
+        assert_eq!(alert_times(&config, &temperatures), [2, 5]);
+
+        // A steady temperature ends the rapid change, so the next one alerts
+        // again within the cooldown: 9°C over 4 seconds
+        let cooldown = format!("{}\ncooldown_seconds = 60", config);
+        let temperatures = [20.0, 24.0, 24.0, 24.0, 24.0, 33.0];
+        assert_eq!(alert_times(&cooldown, &temperatures), [1, 5]);
+
+        let config = AlertConfig::parse(&config, TemperatureUnit::Celsius).unwrap();
+        let mut manager = AlertManager::default();
+        let start = Instant::now();
+        manager.check(&reading(22.0), &config, start);
+        let alerts = manager.check(&reading(18.8), &config, start + Duration::from_secs(1));
+        assert_eq!(alerts[0].kind, AlertKind::Rate);
+        assert_eq!(alerts[0].message, "Temperature falling rapidly: 3.2°C/s");
+    }
+
+    #[test]
+    fn restored_conditions_keep_their_state() {
+        let path = std::env::temp_dir().join(format!("alert_state_{}.json", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let _ = fs::remove_file(&path);
+        let now = Instant::now();
+        let min_duration = Duration::from_secs(60);
+
+        let mut manager = AlertManager::default();
+        assert!(manager.temperature.holds(true, Duration::ZERO, now));
+        assert!(!manager.humidity.holds(true, min_duration, now));
+        let mut state = AlertStateFile::new(&path);
+        state.save(&manager).unwrap();
+
+        let mut restored = AlertStateFile::new(&path)
+            .load(TemperatureUnit::Celsius, now)
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(
+            restored
+                .timer(Channel::Temperature, AlertKind::High)
+                .fired_before_restart
+        );
+        assert!(
+            !restored
+                .timer(Channel::Humidity, AlertKind::High)
+                .fired_before_restart
+        );
+        assert!(
+            !restored
+                .timer(Channel::Light, AlertKind::High)
+                .fired_before_restart
+        );
+        // The humidity condition keeps counting towards its minimum duration
+        let later = now + min_duration;
+        assert!(restored.humidity.holds(true, min_duration, later));
+        // Once a condition clears, it alerts afresh
+        restored.temperature.holds(false, Duration::ZERO, later);
+        assert!(
+            !restored
+                .timer(Channel::Temperature, AlertKind::High)
+                .fired_before_restart
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use crate::config::Config;
+use crate::logger::{is_maintenance_line, parse_log_line, parse_run_line};
+use crate::sensor::Reading;
+use crate::simulation::format_tags;
+use crate::stats::{calculate_statistics, percentile};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+
+// Suggest thresholds just above the 95th percentile of each logged sensor,
+// leaving out rows recorded during maintenance
+pub fn suggest_thresholds(log_path: &str, config: &Config) -> Result<(), Box<dyn Error>> {
+    let current = &config.alerts;
+    let nan = config.storage.nan_representation;
+    let logs = fs::read_to_string(log_path)?;
+    let readings: Vec<Reading> = logs
+        .lines()
+        .filter(|line| !is_maintenance_line(line))
+        .filter_map(|line| parse_log_line(line, nan))
+        .map(|(_, reading)| reading)
+        .collect();
+    if readings.is_empty() {
+        return Err(format!("No readings found in {}", log_path).into());
+    }
+
+    println!(
+        "=== Threshold Suggestions ({} readings) ===",
+        readings.len()
+    );
+    suggest_threshold(
+        "temperature",
+        config.display.temperature_unit.symbol(),
+        readings.iter().map(|r| r.temperature).collect(),
+        current.temperature_threshold,
+        f32::INFINITY,
+    );
+    suggest_threshold(
+        "humidity",
+        "%",
+        readings.iter().map(|r| r.humidity).collect(),
+        current.humidity_threshold,
+        100.0,
+    );
+    suggest_threshold(
+        "light",
+        "%",
+        readings.iter().map(|r| r.light).collect(),
+        current.light_threshold,
+        100.0,
+    );
+    Ok(())
+}
+
+fn suggest_threshold(name: &str, unit: &str, mut values: Vec<f32>, current: f32, max: f32) {
+    // Readings from failed sensors say nothing about the normal range
+    values.retain(|value| value.is_finite());
+    values.sort_by(f32::total_cmp);
+    if let Some(p95) = percentile(&values, 95.0) {
+        println!(
+            "95th percentile of {} was {:.1}{}; consider a threshold of {:.0}{} (current: {}{})",
+            name,
+            p95,
+            unit,
+            p95.ceil().min(max),
+            unit,
+            current,
+            unit
+        );
+    }
+}
+
+// Number of equal-width histogram bins per sensor
+const HISTOGRAM_BINS: usize = 10;
+
+// Width of the longest histogram bar in the printed summary
+const HISTOGRAM_WIDTH: usize = 40;
+
+#[derive(Serialize)]
+struct HistogramBin {
+    lower: f32,
+    upper: f32,
+    count: usize,
+}
+
+// Summary of one sensor's logged readings
+#[derive(Serialize)]
+struct SensorSummary {
+    samples: usize,
+    // Readings logged as NaN, e.g. from a failed sensor
+    invalid: usize,
+    mean: f32,
+    min: f32,
+    max: f32,
+    std: f32,
+    p50: f32,
+    p90: f32,
+    p95: f32,
+    p99: f32,
+    threshold: f32,
+    // Rows above the current threshold
+    exceedances: usize,
+    histogram: Vec<HistogramBin>,
+}
+
+// A run recorded in the log, with its `--tag` metadata
+#[derive(Serialize)]
+struct RunInfo {
+    run_id: String,
+    tags: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct LogSummary {
+    log: String,
+    rows: usize,
+    runs: Vec<RunInfo>,
+    temperature: Option<SensorSummary>,
+    humidity: Option<SensorSummary>,
+    light: Option<SensorSummary>,
+}
+
+// Summarize a recorded log in one pass, without the timing of a replay
+pub fn analyze_log(log_path: &str, config: &Config, json: bool) -> Result<(), Box<dyn Error>> {
+    let summary = summarize_log(log_path, config)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+
+    println!(
+        "=== Log Summary: {} ({} rows) ===",
+        summary.log, summary.rows
+    );
+    for run in &summary.runs {
+        if run.tags.is_empty() {
+            println!("Run {}", run.run_id);
+        } else {
+            println!("Run {} ({})", run.run_id, format_tags(&run.tags));
+        }
+    }
+    for (name, unit, sensor) in [
+        (
+            "Temperature",
+            config.display.temperature_unit.symbol(),
+            &summary.temperature,
+        ),
+        ("Humidity", "%", &summary.humidity),
+        ("Light", "%", &summary.light),
+    ] {
+        print_sensor_summary(name, unit, sensor.as_ref());
+    }
+    Ok(())
+}
+
+// Write the `analyze --json` summary of the log at `log_path` to `export_path`
+pub fn export_log_summary(
+    log_path: &str,
+    config: &Config,
+    export_path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let summary = summarize_log(log_path, config)?;
+    let temp_path = format!("{}.tmp", export_path);
+    fs::write(&temp_path, serde_json::to_string_pretty(&summary)?)?;
+    fs::rename(&temp_path, export_path)?;
+    Ok(())
+}
+
+fn summarize_log(log_path: &str, config: &Config) -> Result<LogSummary, Box<dyn Error>> {
+    let nan = config.storage.nan_representation;
+    let logs = fs::read_to_string(log_path)?;
+    let readings: Vec<Reading> = logs
+        .lines()
+        .filter_map(|line| parse_log_line(line, nan))
+        .map(|(_, reading)| reading)
+        .collect();
+    if readings.is_empty() {
+        return Err(format!("No readings found in {}", log_path).into());
+    }
+
+    let alerts = &config.alerts;
+    Ok(LogSummary {
+        log: log_path.to_string(),
+        rows: readings.len(),
+        runs: logs
+            .lines()
+            .filter_map(parse_run_line)
+            .map(|(run_id, tags)| RunInfo { run_id, tags })
+            .collect(),
+        temperature: summarize(
+            readings.iter().map(|r| r.temperature).collect(),
+            alerts.temperature_threshold,
+        ),
+        humidity: summarize(
+            readings.iter().map(|r| r.humidity).collect(),
+            alerts.humidity_threshold,
+        ),
+        light: summarize(
+            readings.iter().map(|r| r.light).collect(),
+            alerts.light_threshold,
+        ),
+    })
+}
+
+fn summarize(mut values: Vec<f32>, threshold: f32) -> Option<SensorSummary> {
+    let total = values.len();
+    values.retain(|value| value.is_finite());
+    values.sort_by(f32::total_cmp);
+    if values.is_empty() {
+        return None;
+    }
+
+    let stats = calculate_statistics(&values)?;
+    let percentile = |p| percentile(&values, p).unwrap_or(f32::NAN);
+    Some(SensorSummary {
+        samples: values.len(),
+        invalid: total - values.len(),
+        mean: stats.average,
+        min: stats.min,
+        max: stats.max,
+        std: stats.std_dev,
+        p50: percentile(50.0),
+        p90: percentile(90.0),
+        p95: percentile(95.0),
+        p99: percentile(99.0),
+        threshold,
+        exceedances: values.iter().filter(|&&value| value > threshold).count(),
+        histogram: histogram(&values, stats.min, stats.max),
+    })
+}
+
+// Equal-width bins from `min` to `max`; the last bin includes `max`
+fn histogram(values: &[f32], min: f32, max: f32) -> Vec<HistogramBin> {
+    let width = (max - min) / HISTOGRAM_BINS as f32;
+    let mut bins: Vec<HistogramBin> = (0..HISTOGRAM_BINS)
+        .map(|i| HistogramBin {
+            lower: min + width * i as f32,
+            upper: min + width * (i + 1) as f32,
+            count: 0,
+        })
+        .collect();
+    for value in values {
+        let index = if width > 0.0 {
+            (((value - min) / width) as usize).min(HISTOGRAM_BINS - 1)
+        } else {
+            0
+        };
+        bins[index].count += 1;
+    }
+    bins
+}
+
+fn print_sensor_summary(name: &str, unit: &str, sensor: Option<&SensorSummary>) {
+    println!();
+    let Some(sensor) = sensor else {
+        println!("{}: no valid readings", name);
+        return;
+    };
+    println!(
+        "{}: {} samples ({} invalid)",
+        name, sensor.samples, sensor.invalid
+    );
+    println!(
+        "  Mean: {:.2}{unit}, Min: {:.2}{unit}, Max: {:.2}{unit}, Std: {:.2}{unit}",
+        sensor.mean,
+        sensor.min,
+        sensor.max,
+        sensor.std,
+        unit = unit
+    );
+    println!(
+        "  P50: {:.2}{unit}, P90: {:.2}{unit}, P95: {:.2}{unit}, P99: {:.2}{unit}",
+        sensor.p50,
+        sensor.p90,
+        sensor.p95,
+        sensor.p99,
+        unit = unit
+    );
+    println!(
+        "  Above threshold ({}{}): {} row(s)",
+        sensor.threshold, unit, sensor.exceedances
+    );
+    let largest = sensor
+        .histogram
+        .iter()
+        .map(|bin| bin.count)
+        .max()
+        .unwrap_or(0);
+    for bin in &sensor.histogram {
+        let bar = bin.count * HISTOGRAM_WIDTH / largest.max(1);
+        println!(
+            "  {:>8.2} - {:>8.2} | {:<width$} {}",
+            bin.lower,
+            bin.upper,
+            "#".repeat(bar),
+            bin.count,
+            width = HISTOGRAM_WIDTH
+        );
+    }
+}
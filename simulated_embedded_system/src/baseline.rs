@@ -0,0 +1,81 @@
+// Statistics of a known-good reference run, to compare live readings against.
+// A baseline is the JSON summary of a log, as written by `analyze --json` or
+// the `export` action; only each sensor's mean and standard deviation are used.
+
+use crate::bus::Channel;
+use serde::Deserialize;
+use std::error::Error;
+use std::fs;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct SensorBaseline {
+    pub mean: f32,
+    pub std: f32,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Baseline {
+    #[serde(default)]
+    pub temperature: Option<SensorBaseline>,
+    #[serde(default)]
+    pub humidity: Option<SensorBaseline>,
+    #[serde(default)]
+    pub light: Option<SensorBaseline>,
+}
+
+impl Baseline {
+    pub fn load(path: &str) -> Result<Baseline, Box<dyn Error>> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("baseline {}: {}", path, e))?;
+        serde_json::from_str(&contents).map_err(|e| format!("baseline {}: {}", path, e).into())
+    }
+
+    fn sensor(&self, channel: Channel) -> Option<SensorBaseline> {
+        match channel {
+            Channel::Temperature => self.temperature,
+            Channel::Humidity => self.humidity,
+            Channel::Light => self.light,
+            Channel::Pressure | Channel::Co2 => None,
+        }
+    }
+
+    // Standard deviations `value` lies from the baseline mean; `None` for
+    // sensors without a baseline spread or readings that failed
+    pub fn deviation(&self, channel: Channel, value: f32) -> Option<f32> {
+        let baseline = self.sensor(channel)?;
+        (value.is_finite() && baseline.std.is_finite() && baseline.std > 0.0)
+            .then(|| (value - baseline.mean) / baseline.std)
+    }
+
+    // Deviation for the display, e.g. `+2.1σ above baseline`
+    pub fn describe(&self, channel: Channel, value: f32) -> Option<String> {
+        let sigmas = self.deviation(channel, value)?;
+        let direction = if sigmas < 0.0 { "below" } else { "above" };
+        Some(format!("{:+.1}σ {} baseline", sigmas, direction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deviation_is_measured_in_baseline_standard_deviations() {
+        let baseline: Baseline = serde_json::from_str(
+            r#"{"rows": 3, "temperature": {"mean": 22.0, "std": 1.5, "p50": 22.0},
+                "humidity": {"mean": 40.0, "std": 0.0}, "light": null}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            baseline.describe(Channel::Temperature, 25.15).as_deref(),
+            Some("+2.1σ above baseline")
+        );
+        assert_eq!(
+            baseline.describe(Channel::Temperature, 21.4).as_deref(),
+            Some("-0.4σ below baseline")
+        );
+        // No spread, no reading or no baseline for the sensor
+        assert_eq!(baseline.deviation(Channel::Humidity, 41.0), None);
+        assert_eq!(baseline.deviation(Channel::Temperature, f32::NAN), None);
+        assert_eq!(baseline.deviation(Channel::Light, 50.0), None);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::config::BusConfig;
+use crate::sensor::Sensor;
+use crate::stats::JitterStats;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Input channels of the shared ADC, one per sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Temperature,
+    Humidity,
+    Light,
+    Pressure,
+    Co2,
+}
+
+// Time from a read request until its conversion completed, per channel
+#[derive(Default)]
+pub struct ReadLatency {
+    pub temperature: JitterStats,
+    pub humidity: JitterStats,
+    pub light: JitterStats,
+}
+
+impl ReadLatency {
+    // Latency of the sensor on `channel`; only the core sensors are tracked
+    pub fn channel(&mut self, channel: Channel) -> Option<&mut JitterStats> {
+        match channel {
+            Channel::Temperature => Some(&mut self.temperature),
+            Channel::Humidity => Some(&mut self.humidity),
+            Channel::Light => Some(&mut self.light),
+            Channel::Pressure | Channel::Co2 => None,
+        }
+    }
+
+    pub fn with_reservoir(capacity: Option<usize>, seed: Option<u64>) -> Self {
+        ReadLatency {
+            temperature: JitterStats::with_reservoir(capacity, seed),
+            humidity: JitterStats::with_reservoir(capacity, seed),
+            light: JitterStats::with_reservoir(capacity, seed),
+        }
+    }
+}
+
+// A single ADC multiplexed between the sensors. Sensors queue read requests
+// and the bus converts one channel at a time, so later requests wait.
+pub struct AdcBus {
+    conversion_time: Duration,
+    queue: VecDeque<(Channel, Instant)>,
+}
+
+impl AdcBus {
+    pub fn new(config: &BusConfig) -> Self {
+        AdcBus {
+            conversion_time: Duration::from_millis(config.conversion_time_ms),
+            queue: VecDeque::new(),
+        }
+    }
+
+    pub fn request(&mut self, channel: Channel) {
+        self.queue.push_back((channel, Instant::now()));
+    }
+
+    // Convert the queued channels in request order, recording each read's latency
+    pub fn convert(
+        &mut self,
+        sensors: &mut [Box<dyn Sensor>],
+        latency: &mut ReadLatency,
+    ) -> Vec<(Channel, f32)> {
+        let mut values = Vec::with_capacity(self.queue.len());
+        while let Some((channel, requested)) = self.queue.pop_front() {
+            thread::sleep(self.conversion_time);
+            let Some(sensor) = sensors
+                .iter_mut()
+                .find(|sensor| sensor.channel() == channel)
+            else {
+                continue;
+            };
+            let value = sensor.read();
+            if let Some(latency) = latency.channel(channel) {
+                latency.record(requested.elapsed());
+            }
+            values.push((channel, value));
+        }
+        values
+    }
+}
@@ -0,0 +1,43 @@
+// Wall-clock time stamped on log records. Runs use the system's local time;
+// tests can supply a fixed or stepped clock so whole logs can be compared.
+
+use chrono::{DateTime, Duration, Local};
+
+pub trait Clock {
+    // The current time, advancing the clock if it steps
+    fn now(&mut self) -> DateTime<Local>;
+}
+
+// The system's local time
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&mut self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+// Starts at `start` and moves on by `step` every time it is read. A zero step
+// gives a fixed clock.
+pub struct SteppedClock {
+    next: DateTime<Local>,
+    step: Duration,
+}
+
+impl SteppedClock {
+    pub fn new(start: DateTime<Local>, step: Duration) -> Self {
+        SteppedClock { next: start, step }
+    }
+
+    pub fn fixed(time: DateTime<Local>) -> Self {
+        SteppedClock::new(time, Duration::zero())
+    }
+}
+
+impl Clock for SteppedClock {
+    fn now(&mut self) -> DateTime<Local> {
+        let now = self.next;
+        self.next = now + self.step;
+        now
+    }
+}
@@ -0,0 +1,2178 @@
+use crate::alerts::AlertKind;
+use crate::bus::Channel;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+// Configuration structure
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Config {
+    // Write settings changed in the menu back to the config file on exit
+    #[serde(default)]
+    pub auto_save_settings: bool,
+    // What the menu does once stdin is closed, e.g. when input is piped
+    #[serde(default)]
+    pub on_stdin_eof: StdinEof,
+    // Full-screen menus, or a one-line prompt taking chained commands
+    #[serde(default)]
+    pub menu_style: MenuStyle,
+    pub sensors: SensorConfig,
+    pub storage: StorageConfig,
+    pub display: DisplayConfig,
+    pub alerts: AlertConfig,
+    pub server: Option<ServerConfig>,
+    #[serde(default)]
+    pub supervisor: SupervisorConfig,
+    #[serde(default)]
+    pub run: RunConfig,
+    #[serde(default)]
+    pub exit_codes: ExitCodeConfig,
+    #[serde(default)]
+    pub stats: StatsConfig,
+    pub stats_output: Option<StatsOutputConfig>,
+    // Needs the `statsd` feature
+    pub statsd: Option<StatsdConfig>,
+    // Needs the `mqtt` feature
+    pub mqtt: Option<MqttConfig>,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    // Where the config was loaded from, for reloads
+    #[serde(skip)]
+    pub source: String,
+    // Values set from `ESS_*` variables rather than the file
+    #[serde(skip)]
+    env_overrides: Vec<EnvOverride>,
+}
+
+// The keys one `ESS_*` variable changed, as `(section, key, file value,
+// environment value)`, with `None` for a key that is unset
+#[derive(Debug, Default)]
+struct EnvOverride {
+    changes: Vec<(String, String, Option<toml::Value>, Option<toml::Value>)>,
+}
+
+impl EnvOverride {
+    fn between(before: &toml::Value, after: &toml::Value) -> Self {
+        let mut changes = Vec::new();
+        for (section, table) in after.as_table().into_iter().flatten() {
+            let previous = before.get(section);
+            let keys: BTreeSet<&String> = [previous, Some(table)]
+                .into_iter()
+                .flatten()
+                .filter_map(toml::Value::as_table)
+                .flat_map(|table| table.keys())
+                .collect();
+            for key in keys {
+                let file = previous.and_then(|table| table.get(key));
+                let env = table.get(key);
+                if file != env {
+                    changes.push((section.clone(), key.clone(), file.cloned(), env.cloned()));
+                }
+            }
+        }
+        EnvOverride { changes }
+    }
+
+    // Put the file's values back into `values`, as long as none of the keys
+    // have changed since the override
+    fn restore(&self, values: &mut toml::Value) {
+        let unchanged = self.changes.iter().all(|(section, key, _, env)| {
+            values.get(section).and_then(|table| table.get(key)) == env.as_ref()
+        });
+        if !unchanged {
+            return;
+        }
+        for (section, key, file, _) in &self.changes {
+            let Some(table) = values.get_mut(section).and_then(toml::Value::as_table_mut) else {
+                continue;
+            };
+            match file {
+                Some(value) => table.insert(key.clone(), value.clone()),
+                None => table.remove(key),
+            };
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SensorConfig {
+    // Time between samples in seconds, or in milliseconds with the `_ms` keys.
+    // Each core sensor needs one of the two.
+    pub temperature_sampling_rate: Option<u64>,
+    pub humidity_sampling_rate: Option<u64>,
+    pub light_sampling_rate: Option<u64>,
+    pub temperature_sampling_rate_ms: Option<u64>,
+    pub humidity_sampling_rate_ms: Option<u64>,
+    pub light_sampling_rate_ms: Option<u64>,
+    // Optional air-quality sensors, only simulated when their rate is set
+    pub pressure_sampling_rate: Option<u64>,
+    pub co2_sampling_rate: Option<u64>,
+    pub pressure_sampling_rate_ms: Option<u64>,
+    pub co2_sampling_rate_ms: Option<u64>,
+    // Text or CSV log whose readings the core sensors return instead of
+    // simulated values
+    pub replay_file: Option<String>,
+    #[serde(default)]
+    pub temperature_transient: TransientConfig,
+    #[serde(default)]
+    pub humidity_transient: TransientConfig,
+    #[serde(default)]
+    pub light_transient: TransientConfig,
+    // Baseline, noise and drift of each sensor's simulated values, e.g.
+    // `[sensors.temperature_model]`; uniform over the sensor's range when absent
+    pub temperature_model: Option<SensorModel>,
+    pub humidity_model: Option<SensorModel>,
+    pub light_model: Option<SensorModel>,
+    // Share one ADC between the sensors, e.g. `[sensors.bus]`
+    pub bus: Option<BusConfig>,
+    // Vary each sampling interval randomly by up to this many milliseconds
+    #[serde(default)]
+    pub sampling_dither_ms: u64,
+    // Chance from 0 to 1 that a scheduled read drops out and returns nothing
+    #[serde(default)]
+    pub failure_rate: f64,
+    // Time between iterations of the run loop; samples are taken on these ticks
+    #[serde(default = "default_tick_interval_ms")]
+    pub tick_interval_ms: u64,
+    // How sampling rates that aren't a multiple of the tick are handled
+    #[serde(default)]
+    pub off_tick_rates: OffTickRates,
+    // Seed for reproducible sensor values and dither; a fresh seed is used
+    // when absent
+    pub seed: Option<u64>,
+    // Smoothing of each sensor's readings, e.g. `[sensors.temperature_filter]`
+    pub temperature_filter: Option<FilterConfig>,
+    pub humidity_filter: Option<FilterConfig>,
+    pub light_filter: Option<FilterConfig>,
+    // How stateful processors such as the filters start on a sensor's first
+    // reading
+    #[serde(default)]
+    pub first_sample: FirstSample,
+    // Starting values for `first_sample = "seed_from_config"`, e.g.
+    // `[sensors.initial_values]`
+    #[serde(default)]
+    pub initial_values: InitialValues,
+    // Where each sensor's values come from, e.g. `[sensors.temperature_source]`
+    #[serde(default)]
+    pub temperature_source: SensorSource,
+    #[serde(default)]
+    pub humidity_source: SensorSource,
+    #[serde(default)]
+    pub light_source: SensorSource,
+    // Readiness check before the run starts, e.g. `[sensors.temperature_gate]`
+    pub temperature_gate: Option<GateConfig>,
+    pub humidity_gate: Option<GateConfig>,
+    pub light_gate: Option<GateConfig>,
+}
+
+// Scheduling of a sampling rate that the tick can't hit exactly
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OffTickRates {
+    // Warn at startup; each sample is taken on the first tick after it is due,
+    // so the sensor is sampled less often than configured
+    #[default]
+    Warn,
+    // Carry each sample's lateness over to the next one, so the average rate
+    // matches the configured one
+    Accumulate,
+}
+
+impl fmt::Display for OffTickRates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OffTickRates::Warn => write!(f, "warn"),
+            OffTickRates::Accumulate => write!(f, "accumulate"),
+        }
+    }
+}
+
+fn default_tick_interval_ms() -> u64 {
+    1000
+}
+
+// What a stateful per-sensor processor does with the sensor's first reading,
+// before it has a previous value or warmed-up history
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FirstSample {
+    // The first reading only starts the state; the processor has no output
+    // for it, so it is logged as NaN
+    Skip,
+    // The state starts from the first reading
+    #[default]
+    SeedFromFirst,
+    // The state starts as if the sensor had been reading its value under
+    // `[sensors.initial_values]`
+    SeedFromConfig,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize)]
+pub struct InitialValues {
+    pub temperature: Option<f32>,
+    pub humidity: Option<f32>,
+    pub light: Option<f32>,
+}
+
+// Startup health gate of one sensor: the run only starts once the sensor
+// returns a finite reading within `min`..=`max`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GateConfig {
+    #[serde(default = "default_gate_attempts")]
+    pub attempts: u32,
+    // Pause between attempts
+    #[serde(default = "default_gate_interval_ms")]
+    pub interval_ms: u64,
+    // The sensor fails the gate once this has elapsed, even with attempts left
+    #[serde(default = "default_gate_timeout_ms")]
+    pub timeout_ms: u64,
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+}
+
+impl GateConfig {
+    pub fn accepts(&self, value: f32) -> bool {
+        value.is_finite()
+            && self.min.is_none_or(|min| value >= min)
+            && self.max.is_none_or(|max| value <= max)
+    }
+}
+
+fn default_gate_attempts() -> u32 {
+    5
+}
+
+fn default_gate_interval_ms() -> u64 {
+    500
+}
+
+fn default_gate_timeout_ms() -> u64 {
+    5000
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SensorSource {
+    // Simulated values
+    #[default]
+    Virtual,
+    // A metric of the host; needs the `system-metrics` feature
+    System {
+        metric: SystemMetric,
+        // A read taking longer is abandoned and the last value kept
+        #[serde(default = "default_read_timeout_ms")]
+        read_timeout_ms: u64,
+    },
+}
+
+pub fn default_read_timeout_ms() -> u64 {
+    1000
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemMetric {
+    // °C from the first readable thermal zone
+    CpuTemperature,
+    // One-minute load average
+    LoadAverage,
+    // Available memory in % of the total
+    FreeMemory,
+}
+
+impl SystemMetric {
+    pub const ALL: [SystemMetric; 3] = [
+        SystemMetric::CpuTemperature,
+        SystemMetric::LoadAverage,
+        SystemMetric::FreeMemory,
+    ];
+}
+
+// Names as written in the config, e.g. `system (load_average)`
+impl fmt::Display for SensorSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SensorSource::Virtual => write!(f, "virtual"),
+            SensorSource::System { metric, .. } => {
+                let metric = match metric {
+                    SystemMetric::CpuTemperature => "cpu_temperature",
+                    SystemMetric::LoadAverage => "load_average",
+                    SystemMetric::FreeMemory => "free_memory",
+                };
+                write!(f, "system ({})", metric)
+            }
+        }
+    }
+}
+
+// Digital filter applied to a sensor's raw readings
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FilterConfig {
+    // First-order IIR low-pass: each output moves `alpha` (0-1] of the way
+    // from the previous output towards the new reading
+    LowPass { alpha: f32 },
+    // FIR moving average over the last `taps` readings
+    MovingAverage { taps: usize },
+}
+
+impl FilterConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            FilterConfig::LowPass { alpha } if !(alpha > 0.0 && alpha <= 1.0) => Err(format!(
+                "low_pass filter alpha must be in (0, 1], got {}",
+                alpha
+            )),
+            FilterConfig::MovingAverage { taps: 0 } => {
+                Err("moving_average filter needs at least 1 tap".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl SensorConfig {
+    // Time between samples of the sensor on `channel`, if it is configured
+    pub fn sampling_rate(&self, channel: Channel) -> Option<Duration> {
+        let (seconds, millis) = self.sampling_rate_keys(channel);
+        match millis {
+            Some(millis) => Some(Duration::from_millis(millis)),
+            None => seconds.map(Duration::from_secs),
+        }
+    }
+
+    // Set the sampling rate of the sensor on `channel` in milliseconds
+    pub fn set_sampling_rate_ms(&mut self, channel: Channel, millis: u64) {
+        let (seconds, ms) = match channel {
+            Channel::Temperature => (
+                &mut self.temperature_sampling_rate,
+                &mut self.temperature_sampling_rate_ms,
+            ),
+            Channel::Humidity => (
+                &mut self.humidity_sampling_rate,
+                &mut self.humidity_sampling_rate_ms,
+            ),
+            Channel::Light => (
+                &mut self.light_sampling_rate,
+                &mut self.light_sampling_rate_ms,
+            ),
+            Channel::Pressure => (
+                &mut self.pressure_sampling_rate,
+                &mut self.pressure_sampling_rate_ms,
+            ),
+            Channel::Co2 => (&mut self.co2_sampling_rate, &mut self.co2_sampling_rate_ms),
+        };
+        *seconds = None;
+        *ms = Some(millis);
+    }
+
+    // The seconds and milliseconds keys of the sensor on `channel`
+    fn sampling_rate_keys(&self, channel: Channel) -> (Option<u64>, Option<u64>) {
+        match channel {
+            Channel::Temperature => (
+                self.temperature_sampling_rate,
+                self.temperature_sampling_rate_ms,
+            ),
+            Channel::Humidity => (self.humidity_sampling_rate, self.humidity_sampling_rate_ms),
+            Channel::Light => (self.light_sampling_rate, self.light_sampling_rate_ms),
+            Channel::Pressure => (self.pressure_sampling_rate, self.pressure_sampling_rate_ms),
+            Channel::Co2 => (self.co2_sampling_rate, self.co2_sampling_rate_ms),
+        }
+    }
+
+    // Each sensor's rate is set by at most one key, and the core three by
+    // one; a rate of 0 would sample on every tick of a busy loop
+    fn validate_sampling_rates(&self) -> Result<(), String> {
+        for (channel, name, required) in [
+            (Channel::Temperature, "temperature", true),
+            (Channel::Humidity, "humidity", true),
+            (Channel::Light, "light", true),
+            (Channel::Pressure, "pressure", false),
+            (Channel::Co2, "co2", false),
+        ] {
+            match self.sampling_rate_keys(channel) {
+                (Some(_), Some(_)) => {
+                    return Err(format!(
+                        "set only one of {name}_sampling_rate and {name}_sampling_rate_ms"
+                    ))
+                }
+                (None, None) if required => {
+                    return Err(format!(
+                        "{name}_sampling_rate or {name}_sampling_rate_ms must be set"
+                    ))
+                }
+                (Some(0), None) => {
+                    return Err(format!(
+                        "{name}_sampling_rate must be greater than 0, got 0"
+                    ))
+                }
+                (None, Some(0)) => {
+                    return Err(format!(
+                        "{name}_sampling_rate_ms must be greater than 0, got 0"
+                    ))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // With `first_sample = "seed_from_config"`, each sensor that has a
+    // stateful processor needs a starting value
+    fn validate_first_sample(&self) -> Result<(), String> {
+        if self.first_sample != FirstSample::SeedFromConfig {
+            return Ok(());
+        }
+        for (name, filter, initial) in [
+            (
+                "temperature",
+                &self.temperature_filter,
+                self.initial_values.temperature,
+            ),
+            (
+                "humidity",
+                &self.humidity_filter,
+                self.initial_values.humidity,
+            ),
+            ("light", &self.light_filter, self.initial_values.light),
+        ] {
+            if filter.is_some() && initial.is_none() {
+                return Err(format!(
+                    "first_sample = \"seed_from_config\" needs {} under [sensors.initial_values]",
+                    name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Configured sensors whose sampling rate isn't a multiple of the tick
+    pub fn off_tick_channels(&self) -> Vec<Channel> {
+        let tick = Duration::from_millis(self.tick_interval_ms);
+        [
+            Channel::Temperature,
+            Channel::Humidity,
+            Channel::Light,
+            Channel::Pressure,
+            Channel::Co2,
+        ]
+        .into_iter()
+        .filter(|&channel| {
+            self.sampling_rate(channel)
+                .is_some_and(|rate| rate.as_nanos() % tick.as_nanos() != 0)
+        })
+        .collect()
+    }
+
+    // Shortest sampling interval of the configured sensors
+    pub fn min_sampling_interval(&self) -> Duration {
+        [
+            Channel::Temperature,
+            Channel::Humidity,
+            Channel::Light,
+            Channel::Pressure,
+            Channel::Co2,
+        ]
+        .into_iter()
+        .filter_map(|channel| self.sampling_rate(channel))
+        .min()
+        .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BusConfig {
+    pub conversion_time_ms: u64,
+}
+
+// Power-on transient of a sensor, e.g. `[sensors.temperature_transient]`.
+// The offset and noise amplitude decay linearly to zero over `samples` readings.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct TransientConfig {
+    #[serde(default)]
+    pub samples: u32,
+    #[serde(default)]
+    pub offset: f32,
+    #[serde(default)]
+    pub noise: f32,
+}
+
+impl TransientConfig {
+    fn validate(&self) -> Result<(), String> {
+        if !(self.offset.is_finite() && self.noise.is_finite()) {
+            return Err("a sensor transient's offset and noise must be finite numbers".to_string());
+        }
+        if self.noise < 0.0 {
+            return Err(format!(
+                "transient noise must not be negative, got {}",
+                self.noise
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Simulated values around `baseline`, with Gaussian noise of `noise_stddev`
+// and a linear drift of `drift_per_minute` since the sensor started
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SensorModel {
+    pub baseline: f32,
+    #[serde(default)]
+    pub noise_stddev: f32,
+    #[serde(default)]
+    pub drift_per_minute: f32,
+}
+
+impl SensorModel {
+    fn validate(&self) -> Result<(), String> {
+        if ![self.baseline, self.noise_stddev, self.drift_per_minute]
+            .iter()
+            .all(|value| value.is_finite())
+        {
+            return Err("a sensor model's values must be finite numbers".to_string());
+        }
+        if self.noise_stddev < 0.0 {
+            return Err(format!(
+                "noise_stddev must not be negative, got {}",
+                self.noise_stddev
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StorageConfig {
+    pub log_file_path: String,
+    #[serde(default)]
+    pub log_mode: LogMode,
+    #[serde(default)]
+    pub log_format: LogFormat,
+    // Start a new log file every hour or day
+    pub rotation: Option<Rotation>,
+    // Start a new log file before one would grow past this size, keeping
+    // `max_log_files` older ones numbered from 1 (the newest)
+    pub max_log_size_bytes: Option<u64>,
+    #[serde(default = "default_max_log_files")]
+    pub max_log_files: u32,
+    #[serde(default)]
+    pub flush_policy: FlushPolicy,
+    #[serde(default)]
+    pub log_lock: LogLock,
+    #[serde(default)]
+    pub nan_representation: NanRepresentation,
+    // Also log the unfiltered readings after the filtered ones
+    #[serde(default)]
+    pub log_raw_values: bool,
+    #[serde(default)]
+    pub timestamp_resolution: TimestampResolution,
+    // Ship log segments to S3-compatible storage once rotation closes them
+    pub upload: Option<UploadConfig>,
+    // JSON Lines file that every alert is appended to, apart from the log
+    pub alert_log_path: Option<String>,
+}
+
+fn default_max_log_files() -> u32 {
+    5
+}
+
+// S3-compatible bucket for rotated log segments, e.g. `[storage.upload]`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UploadConfig {
+    // e.g. `https://s3.eu-west-1.amazonaws.com` or `http://minio.local:9000`
+    pub endpoint: String,
+    #[serde(default = "default_upload_region")]
+    pub region: String,
+    pub bucket: String,
+    // Prepended to each segment's file name to form its object key
+    #[serde(default)]
+    pub prefix: String,
+    // Taken from AWS_ACCESS_KEY_ID / AWS_SECRET_ACCESS_KEY when absent
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    #[serde(default = "default_upload_retries")]
+    pub retries: u32,
+    // Segments whose upload failed, retried when the next run starts
+    #[serde(default = "default_pending_uploads_file")]
+    pub pending_file: String,
+}
+
+impl UploadConfig {
+    pub fn credentials(&self) -> Result<(String, String), String> {
+        let access_key_id = self
+            .access_key_id
+            .clone()
+            .or_else(|| std::env::var("AWS_ACCESS_KEY_ID").ok())
+            .ok_or("no access_key_id configured and AWS_ACCESS_KEY_ID is not set")?;
+        let secret_access_key = self
+            .secret_access_key
+            .clone()
+            .or_else(|| std::env::var("AWS_SECRET_ACCESS_KEY").ok())
+            .ok_or("no secret_access_key configured and AWS_SECRET_ACCESS_KEY is not set")?;
+        Ok((access_key_id, secret_access_key))
+    }
+}
+
+fn default_upload_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_upload_retries() -> u32 {
+    3
+}
+
+fn default_pending_uploads_file() -> String {
+    "pending_uploads.txt".to_string()
+}
+
+// How text log timestamps keep rows taken within the same second apart
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampResolution {
+    // Milliseconds when any sensor samples more often than once a second
+    #[default]
+    Auto,
+    Seconds,
+    Milliseconds,
+    // Second resolution, with ` #2`, ` #3`, ... added to repeated timestamps
+    Counter,
+}
+
+// How NaN and infinite readings are written to logs: `"nan"`, `"empty"`, or a
+// sentinel number such as `-999`. They are read back as NaN.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "NanRepresentationValue", into = "NanRepresentationValue")]
+pub enum NanRepresentation {
+    #[default]
+    Nan,
+    Empty,
+    Sentinel(f32),
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum NanRepresentationValue {
+    Sentinel(f32),
+    Keyword(String),
+}
+
+impl From<NanRepresentation> for NanRepresentationValue {
+    fn from(nan: NanRepresentation) -> Self {
+        match nan {
+            NanRepresentation::Nan => NanRepresentationValue::Keyword("nan".to_string()),
+            NanRepresentation::Empty => NanRepresentationValue::Keyword("empty".to_string()),
+            NanRepresentation::Sentinel(sentinel) => NanRepresentationValue::Sentinel(sentinel),
+        }
+    }
+}
+
+impl TryFrom<NanRepresentationValue> for NanRepresentation {
+    type Error = String;
+
+    fn try_from(value: NanRepresentationValue) -> Result<Self, Self::Error> {
+        match value {
+            NanRepresentationValue::Sentinel(sentinel) if sentinel.is_finite() => {
+                Ok(NanRepresentation::Sentinel(sentinel))
+            }
+            NanRepresentationValue::Keyword(keyword) if keyword == "nan" => {
+                Ok(NanRepresentation::Nan)
+            }
+            NanRepresentationValue::Keyword(keyword) if keyword == "empty" => {
+                Ok(NanRepresentation::Empty)
+            }
+            _ => {
+                Err("nan_representation must be \"nan\", \"empty\" or a finite number".to_string())
+            }
+        }
+    }
+}
+
+// When buffered text log records are written through to disk
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlushPolicy {
+    // Every record
+    #[default]
+    Always,
+    // Only alerts; readings stay buffered until an alert or the end of the run
+    Important,
+}
+
+// How the text log guards against other processes writing the same file,
+// using an advisory lock
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLock {
+    // Hold the lock while the file is open; a second instance fails to start
+    #[default]
+    Fail,
+    // Take the lock around each record, so instances take turns writing.
+    // Every record is flushed as it is written.
+    Serialize,
+    // No locking
+    Off,
+}
+
+// Whether a run appends to the log file or starts it afresh
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogMode {
+    #[default]
+    Append,
+    Truncate,
+}
+
+// Period after which the text log moves on to a new file
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    Hourly,
+    Daily,
+}
+
+// On-disk format of the log file
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    // Comma-separated readings under a header row, for spreadsheets
+    Csv,
+    // One JSON object per line, for log pipelines
+    Json,
+    // Typed columns for analytics tools; needs the `parquet` feature
+    Parquet,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DisplayConfig {
+    pub real_time_graph: bool,
+    // Seconds between statistics lines; 0 shows them with every frame
+    #[serde(default)]
+    pub stats_display_every_secs: u64,
+    // Frames beyond this rate, or that the terminal can't keep up with, are skipped
+    #[serde(default = "default_max_refresh_hz")]
+    pub max_refresh_hz: u32,
+    // Minimum time between console updates; takes precedence over `max_refresh_hz`
+    #[serde(default)]
+    pub min_update_interval_ms: Option<u64>,
+    // Color graph bars by how fast the value changes, on terminals without `NO_COLOR`
+    #[serde(default)]
+    pub rate_colors: bool,
+    // Displays that are active at once
+    #[serde(default = "default_display_outputs")]
+    pub outputs: Vec<DisplayOutput>,
+    // File the snapshot display keeps the latest frame in
+    #[serde(default = "default_snapshot_path")]
+    pub snapshot_path: String,
+    // PNG the image display renders the current state to, and how often
+    #[serde(default = "default_image_path")]
+    pub image_path: String,
+    #[serde(default = "default_image_interval_secs")]
+    pub image_interval_secs: u64,
+    // TrueType font for the image's text
+    #[serde(default = "default_image_font")]
+    pub image_font: String,
+    // Recent values kept for each sensor's graph and rolling statistics
+    #[serde(default)]
+    pub window_size: Option<usize>,
+    // How a graph is drawn before its window has filled up
+    #[serde(default)]
+    pub partial_window: PartialWindow,
+    // Summary of a reference run, as written by `analyze --json`, to show
+    // each reading's deviation from
+    #[serde(default)]
+    pub baseline_path: Option<String>,
+    // Port to serve Prometheus metrics on at `/metrics`; no server when unset
+    #[serde(default)]
+    pub metrics_port: Option<u16>,
+    // Unit temperatures are shown, logged and alerted on in
+    #[serde(default)]
+    pub temperature_unit: TemperatureUnit,
+    #[serde(default)]
+    pub temperature: SensorDisplayConfig,
+    #[serde(default)]
+    pub humidity: SensorDisplayConfig,
+    #[serde(default)]
+    pub light: SensorDisplayConfig,
+}
+
+impl DisplayConfig {
+    // Values kept in the rolling window of a sensor shown with `label`; 0 or
+    // unset means the default of 10
+    pub fn window_size(&self, label: &SensorDisplayConfig) -> usize {
+        match label.window_size.or(self.window_size) {
+            None | Some(0) => default_window_size(),
+            Some(size) => size,
+        }
+    }
+
+    // Display settings of the sensor on `channel`, for the sensors that have them
+    pub fn sensor(&self, channel: Channel) -> Option<&SensorDisplayConfig> {
+        match channel {
+            Channel::Temperature => Some(&self.temperature),
+            Channel::Humidity => Some(&self.humidity),
+            Channel::Light => Some(&self.light),
+            Channel::Pressure | Channel::Co2 => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayOutput {
+    Console,
+    // The latest frame written to `snapshot_path`
+    Snapshot,
+    // The current state rendered to `image_path`; needs the `imaging` feature
+    Image,
+}
+
+// How a sensor's values are shown in the display, e.g. `[display.light]`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SensorDisplayConfig {
+    #[serde(default = "default_label_width")]
+    pub width: usize,
+    #[serde(default = "default_label_precision")]
+    pub precision: usize,
+    // Recent values kept for the sensor's graph and rolling statistics;
+    // overrides `window_size` under `[display]`
+    #[serde(default)]
+    pub window_size: Option<usize>,
+    // Change from the previous sample at which a graph bar turns yellow, and red
+    #[serde(default = "default_moderate_change")]
+    pub moderate_change: f32,
+    #[serde(default = "default_rapid_change")]
+    pub rapid_change: f32,
+    // Color of the sensor in the status line, graph and statistics
+    #[serde(default)]
+    pub color: Option<SensorColor>,
+    // Value the graph's bars start from
+    #[serde(default)]
+    pub graph_baseline: GraphBaseline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SensorColor {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+}
+
+impl Default for SensorDisplayConfig {
+    fn default() -> Self {
+        SensorDisplayConfig {
+            width: default_label_width(),
+            precision: default_label_precision(),
+            window_size: None,
+            moderate_change: default_moderate_change(),
+            rapid_change: default_rapid_change(),
+            color: None,
+            graph_baseline: GraphBaseline::default(),
+        }
+    }
+}
+
+// Sensors read in Celsius; readings are converted to this unit after
+// filtering, before they are displayed, logged or checked against the alerts
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+        }
+    }
+
+    pub fn from_celsius(self, celsius: f32) -> f32 {
+        match self {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    pub fn to_celsius(self, value: f32) -> f32 {
+        match self {
+            TemperatureUnit::Celsius => value,
+            TemperatureUnit::Fahrenheit => (value - 32.0) * 5.0 / 9.0,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PartialWindow {
+    // Only the values so far, so the graph grows
+    #[default]
+    Grow,
+    // Empty rows at the baseline before the values, so the newest value is
+    // always on the last row
+    Pad,
+    // The values so far followed by empty rows, so the graph has its full height
+    LeftAlign,
+    // No graph until the window is full
+    Hide,
+}
+
+// Where graph bars start: `"zero"`, `"window_min"` (the lowest value in the
+// graph) or a fixed value such as `20`. Bars only show the part of a value
+// above the baseline.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "GraphBaselineValue", into = "GraphBaselineValue")]
+pub enum GraphBaseline {
+    #[default]
+    Zero,
+    WindowMin,
+    Fixed(f32),
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum GraphBaselineValue {
+    Fixed(f32),
+    Keyword(String),
+}
+
+impl From<GraphBaseline> for GraphBaselineValue {
+    fn from(baseline: GraphBaseline) -> Self {
+        match baseline {
+            GraphBaseline::Zero => GraphBaselineValue::Keyword("zero".to_string()),
+            GraphBaseline::WindowMin => GraphBaselineValue::Keyword("window_min".to_string()),
+            GraphBaseline::Fixed(value) => GraphBaselineValue::Fixed(value),
+        }
+    }
+}
+
+impl TryFrom<GraphBaselineValue> for GraphBaseline {
+    type Error = String;
+
+    fn try_from(value: GraphBaselineValue) -> Result<Self, Self::Error> {
+        match value {
+            GraphBaselineValue::Fixed(value) if value.is_finite() => {
+                Ok(GraphBaseline::Fixed(value))
+            }
+            GraphBaselineValue::Keyword(keyword) if keyword == "zero" => Ok(GraphBaseline::Zero),
+            GraphBaselineValue::Keyword(keyword) if keyword == "window_min" => {
+                Ok(GraphBaseline::WindowMin)
+            }
+            _ => Err(
+                "graph_baseline must be \"zero\", \"window_min\" or a finite number".to_string(),
+            ),
+        }
+    }
+}
+
+fn default_max_refresh_hz() -> u32 {
+    10
+}
+
+fn default_display_outputs() -> Vec<DisplayOutput> {
+    vec![DisplayOutput::Console]
+}
+
+fn default_snapshot_path() -> String {
+    "display.txt".to_string()
+}
+
+fn default_image_path() -> String {
+    "status.png".to_string()
+}
+
+fn default_image_interval_secs() -> u64 {
+    5
+}
+
+fn default_image_font() -> String {
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf".to_string()
+}
+
+fn default_window_size() -> usize {
+    10
+}
+
+fn default_moderate_change() -> f32 {
+    1.0
+}
+
+fn default_rapid_change() -> f32 {
+    5.0
+}
+
+fn default_label_width() -> usize {
+    5
+}
+
+fn default_label_precision() -> usize {
+    2
+}
+
+// Plausible readings of each kind of sensor, which the alert thresholds must
+// lie in. Temperature covers the operating range of common digital sensors.
+const TEMPERATURE_RANGE: RangeInclusive<f32> = -55.0..=125.0;
+const PERCENT_RANGE: RangeInclusive<f32> = 0.0..=100.0;
+const PRESSURE_RANGE: RangeInclusive<f32> = 300.0..=1100.0;
+const CO2_RANGE: RangeInclusive<f32> = 0.0..=10000.0;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AlertConfig {
+    pub temperature_threshold: f32,
+    pub humidity_threshold: f32,
+    pub light_threshold: f32,
+    // Thresholds of the optional sensors in hPa and ppm
+    pub pressure_threshold: Option<f32>,
+    pub co2_threshold: Option<f32>,
+    // Alert when a reading falls below these
+    pub temperature_min: Option<f32>,
+    pub humidity_min: Option<f32>,
+    pub light_min: Option<f32>,
+    // Alert when the temperature changes faster than this many degrees per
+    // second, in `display.temperature_unit`
+    pub temperature_rate_threshold: Option<f32>,
+    // How long a condition must hold continuously before its alert fires
+    #[serde(default)]
+    pub temperature_alert_min_duration_secs: u64,
+    #[serde(default)]
+    pub humidity_alert_min_duration_secs: u64,
+    #[serde(default)]
+    pub light_alert_min_duration_secs: u64,
+    // Repeats of an ongoing alert within this many seconds are suppressed
+    #[serde(default)]
+    pub cooldown_seconds: u64,
+    // Where each sensor's alerts are sent; every channel by default
+    #[serde(default = "all_alert_channels")]
+    pub temperature_alert_channels: Vec<AlertChannel>,
+    #[serde(default = "all_alert_channels")]
+    pub humidity_alert_channels: Vec<AlertChannel>,
+    #[serde(default = "all_alert_channels")]
+    pub light_alert_channels: Vec<AlertChannel>,
+    #[serde(default = "all_alert_channels")]
+    pub pressure_alert_channels: Vec<AlertChannel>,
+    #[serde(default = "all_alert_channels")]
+    pub co2_alert_channels: Vec<AlertChannel>,
+    // Alerts that are still counted but never shown or sent anywhere
+    #[serde(default)]
+    pub suppress: Vec<SuppressRule>,
+    // Optional separate file with these same keys, hot-reloaded during a run
+    pub thresholds_file: Option<String>,
+}
+
+// An entry of `suppress`: every alert of a sensor, such as `"humidity"`, or
+// one of its rules, such as `"temperature.low"`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SuppressRule {
+    pub sensor: Channel,
+    // Both kinds when unset
+    pub kind: Option<AlertKind>,
+}
+
+impl From<SuppressRule> for String {
+    fn from(rule: SuppressRule) -> Self {
+        let sensor = format!("{:?}", rule.sensor).to_lowercase();
+        match rule.kind {
+            Some(kind) => format!("{}.{}", sensor, kind),
+            None => sensor,
+        }
+    }
+}
+
+impl TryFrom<String> for SuppressRule {
+    type Error = String;
+
+    fn try_from(rule: String) -> Result<Self, Self::Error> {
+        let (sensor, kind) = match rule.split_once('.') {
+            Some((sensor, kind)) => (sensor, Some(kind)),
+            None => (rule.as_str(), None),
+        };
+        let sensor = match sensor {
+            "temperature" => Channel::Temperature,
+            "humidity" => Channel::Humidity,
+            "light" => Channel::Light,
+            "pressure" => Channel::Pressure,
+            "co2" => Channel::Co2,
+            _ => {
+                return Err(format!(
+                    "unknown sensor in suppress rule `{}`; expected temperature, humidity, light, pressure or co2",
+                    rule
+                ))
+            }
+        };
+        let kind = match kind {
+            None => None,
+            Some("high") => Some(AlertKind::High),
+            Some("low") => Some(AlertKind::Low),
+            Some("rate") => Some(AlertKind::Rate),
+            Some(_) => {
+                return Err(format!(
+                    "unknown rule in suppress rule `{}`; expected high, low or rate",
+                    rule
+                ))
+            }
+        };
+        Ok(SuppressRule { sensor, kind })
+    }
+}
+
+// Destination an alert can be routed to
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertChannel {
+    // The display outputs
+    Console,
+    // The log file
+    File,
+    Webhook,
+    Syslog,
+}
+
+fn all_alert_channels() -> Vec<AlertChannel> {
+    vec![
+        AlertChannel::Console,
+        AlertChannel::File,
+        AlertChannel::Webhook,
+        AlertChannel::Syslog,
+    ]
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ServerConfig {
+    pub port: u16,
+    #[serde(default = "default_buffer_size")]
+    pub buffer_size: usize,
+}
+
+fn default_buffer_size() -> usize {
+    600
+}
+
+// Run statistics
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct StatsConfig {
+    // Samples kept per statistic for percentiles; all of them when absent.
+    // Must be greater than 0.
+    pub reservoir_size: Option<usize>,
+}
+
+// Periodic statistics rows written to their own file
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StatsOutputConfig {
+    pub path: String,
+    #[serde(default = "default_stats_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_stats_interval_secs() -> u64 {
+    60
+}
+
+// StatsD (or DogStatsD) endpoint the readings are sent to as gauges and the
+// alerts as counters
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StatsdConfig {
+    #[serde(default = "default_statsd_host")]
+    pub host: String,
+    #[serde(default = "default_statsd_port")]
+    pub port: u16,
+    // Start of every metric name, e.g. `simulator.temperature`
+    #[serde(default = "default_statsd_prefix")]
+    pub prefix: String,
+    // Tag the metrics with the run ID and `--tag` values, DogStatsD style
+    #[serde(default)]
+    pub dogstatsd_tags: bool,
+}
+
+fn default_statsd_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_statsd_port() -> u16 {
+    8125
+}
+
+fn default_statsd_prefix() -> String {
+    "simulator".to_string()
+}
+
+// MQTT broker the readings are published to, one topic per sensor
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MqttConfig {
+    pub broker: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    // Start of every topic, e.g. `simulator/temperature`
+    #[serde(default = "default_mqtt_topic_prefix")]
+    pub topic_prefix: String,
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_topic_prefix() -> String {
+    "simulator".to_string()
+}
+
+// Where alerts are sent besides the console and the log
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct NotificationConfig {
+    // Needs the `webhook` feature
+    pub webhook_url: Option<String>,
+    // Send alerts to the local syslog daemon (Unix only)
+    #[serde(default)]
+    pub syslog: bool,
+    // Alerts fired within this many seconds of the first are sent as one notification
+    #[serde(default)]
+    pub alert_group_window_secs: u64,
+}
+
+// Automatic restarts of a simulation that panics
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SupervisorConfig {
+    #[serde(default)]
+    pub max_restarts: u32,
+    // Start the run statistics afresh after a restart
+    #[serde(default = "default_reset_stats")]
+    pub reset_stats: bool,
+    // File the alert conditions are kept in, so an alert that was already
+    // paged isn't paged again after a restart
+    #[serde(default)]
+    pub alert_state_file: Option<String>,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        SupervisorConfig {
+            max_restarts: 0,
+            reset_stats: default_reset_stats(),
+            alert_state_file: None,
+        }
+    }
+}
+
+fn default_reset_stats() -> bool {
+    true
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StdinEof {
+    // Leave the menu
+    #[default]
+    Exit,
+    // Start one run with the default duration, then exit
+    Run,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MenuStyle {
+    // Each menu clears the screen and lists its options
+    #[default]
+    Full,
+    // `[1]run [2]settings ...` on one line without clearing the screen; several
+    // commands can be entered at once, e.g. `3 5`
+    Compact,
+}
+
+// Allowed range of run durations, for standardized test runs, and what
+// happens once a run is over
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct RunConfig {
+    pub min_duration_secs: Option<u64>,
+    pub max_duration_secs: Option<u64>,
+    // What `run --duration` does with a duration outside the range
+    #[serde(default)]
+    pub out_of_range: OutOfRange,
+    // Actions after each run, in order; none returns to the menu
+    #[serde(default)]
+    pub on_complete: Vec<OnComplete>,
+    // File the "export" action writes the log summary to
+    pub export_path: Option<String>,
+    // File each run's summary is appended to, besides printing it
+    pub summary_file: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnComplete {
+    // Back to the menu, or exit after a `run` command
+    Menu,
+    // Start another run with the same settings, unless this one was stopped early
+    Repeat,
+    // Write the summary of the log, as `analyze --json` prints it
+    Export,
+    // Exit the program
+    Exit,
+}
+
+impl FromStr for OnComplete {
+    type Err = String;
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        match action {
+            "menu" => Ok(OnComplete::Menu),
+            "repeat" => Ok(OnComplete::Repeat),
+            "export" => Ok(OnComplete::Export),
+            "exit" => Ok(OnComplete::Exit),
+            _ => Err(format!(
+                "unknown action `{}`; expected menu, repeat, export or exit",
+                action
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutOfRange {
+    #[default]
+    Error,
+    // Run for the nearest allowed duration instead
+    Clamp,
+}
+
+impl RunConfig {
+    // Reject a duration outside `min_duration_secs..=max_duration_secs`
+    pub fn check_duration(&self, seconds: u64) -> Result<(), String> {
+        if let Some(min) = self.min_duration_secs.filter(|&min| seconds < min) {
+            return Err(format!(
+                "a {} second run is shorter than min_duration_secs = {}",
+                seconds, min
+            ));
+        }
+        if let Some(max) = self.max_duration_secs.filter(|&max| seconds > max) {
+            return Err(format!(
+                "a {} second run is longer than max_duration_secs = {}",
+                seconds, max
+            ));
+        }
+        Ok(())
+    }
+
+    // Nearest allowed duration to `seconds`
+    pub fn clamp_duration(&self, seconds: u64) -> u64 {
+        let seconds = self
+            .min_duration_secs
+            .map_or(seconds, |min| seconds.max(min));
+        self.max_duration_secs
+            .map_or(seconds, |max| seconds.min(max))
+    }
+
+    // File the "export" action writes to
+    pub fn export_path(&self) -> &str {
+        self.export_path.as_deref().unwrap_or("run_export.json")
+    }
+}
+
+// Process exit codes for run outcomes; a code of 0 ignores that outcome. The
+// code for a config that can't be loaded isn't here, since the file it would
+// be read from is what failed; see `config_error_exit_code`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ExitCodeConfig {
+    // Any alert fired during the run
+    #[serde(default = "default_alert_exit_code")]
+    pub alert: u8,
+    // Fewer than `min_data_quality` of the records reached the log
+    #[serde(default = "default_data_quality_exit_code")]
+    pub data_quality: u8,
+    #[serde(default = "default_min_data_quality")]
+    pub min_data_quality: f64,
+}
+
+impl Default for ExitCodeConfig {
+    fn default() -> Self {
+        ExitCodeConfig {
+            alert: default_alert_exit_code(),
+            data_quality: default_data_quality_exit_code(),
+            min_data_quality: default_min_data_quality(),
+        }
+    }
+}
+
+// Exit code when the configuration cannot be loaded: 4, or the value of
+// `ESS_EXIT_CODE_CONFIG_ERROR`. An invalid value is reported and ignored,
+// and 0 isn't allowed, so a failed load never looks like a clean run.
+pub fn config_error_exit_code() -> u8 {
+    const DEFAULT: u8 = 4;
+    const VAR: &str = "ESS_EXIT_CODE_CONFIG_ERROR";
+    let Ok(value) = env::var(VAR) else {
+        return DEFAULT;
+    };
+    match value.trim().parse::<u8>() {
+        Ok(code) if code > 0 => code,
+        _ => {
+            eprintln!(
+                "[WARNING]: {}={:?} is not an exit code from 1 to 255, using {}",
+                VAR, value, DEFAULT
+            );
+            DEFAULT
+        }
+    }
+}
+
+fn default_alert_exit_code() -> u8 {
+    2
+}
+
+fn default_data_quality_exit_code() -> u8 {
+    3
+}
+
+fn default_min_data_quality() -> f64 {
+    0.99
+}
+
+// Read a config file as text, skipping a UTF-8 byte order mark that some
+// editors add and pointing at the first invalid byte of other encodings
+fn read_config_file(path: &str) -> Result<String, Box<dyn Error>> {
+    const BOM: &[u8] = b"\xEF\xBB\xBF";
+    let bytes = fs::read(path)?;
+    let offset = if bytes.starts_with(BOM) { BOM.len() } else { 0 };
+    String::from_utf8(bytes[offset..].to_vec()).map_err(|e| {
+        format!(
+            "file is not valid UTF-8 at byte {}",
+            offset + e.utf8_error().valid_up_to()
+        )
+        .into()
+    })
+}
+
+// Deserialize a TOML config, naming the format when the file was replaced
+// with another one, e.g. YAML, rather than reporting the first TOML error
+fn parse_toml<T: serde::de::DeserializeOwned>(config_str: &str) -> Result<T, Box<dyn Error>> {
+    toml::from_str(config_str).map_err(|e| match detect_format(config_str) {
+        Some(format) => format!("expected a TOML file, but it looks like {}", format).into(),
+        None => e.into(),
+    })
+}
+
+// The value of the environment variable `var`, if it is set, reporting the
+// key it overrides
+fn env_override<T>(var: &str) -> Result<Option<T>, String>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let value = match env::var(var) {
+        Ok(value) => value,
+        Err(env::VarError::NotPresent) => return Ok(None),
+        Err(env::VarError::NotUnicode(_)) => return Err(format!("{} is not valid UTF-8", var)),
+    };
+    let parsed = value
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid {}={:?}: {}", var, value, e))?;
+    let key = var.trim_start_matches("ESS_").to_lowercase();
+    println!("[Config]: {} overridden by {}", key, var);
+    Ok(Some(parsed))
+}
+
+// Recognize a config written in a format other than TOML
+fn detect_format(config_str: &str) -> Option<&'static str> {
+    let first_line = config_str
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+    if first_line.starts_with('{') {
+        return Some("JSON");
+    }
+    // YAML documents start with `---` or use `key: value` mappings
+    let yaml_key = first_line.split_once(':').is_some_and(|(key, rest)| {
+        !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            && (rest.is_empty() || rest.starts_with(' '))
+    });
+    (first_line == "---" || yaml_key).then_some("YAML")
+}
+
+// Last successfully fetched remote config, used when the remote is unreachable
+const REMOTE_CONFIG_CACHE: &str = "remote_config_cache.toml";
+
+impl Config {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut config: Config = if is_remote(path) {
+            Config::load_remote(path)?
+        } else {
+            parse_toml(&read_config_file(path)?)?
+        };
+        config.apply_env_overrides()?;
+        config.validate()?;
+        config.source = path.to_string();
+        Ok(config)
+    }
+
+    // Let `ESS_*` environment variables, named after the config keys, take
+    // precedence over the file, e.g. `ESS_LOG_FILE_PATH` for `log_file_path`.
+    // A value that doesn't parse is an error rather than being ignored.
+    pub fn apply_env_overrides(&mut self) -> Result<(), String> {
+        for (channel, name) in [
+            (Channel::Temperature, "TEMPERATURE"),
+            (Channel::Humidity, "HUMIDITY"),
+            (Channel::Light, "LIGHT"),
+            (Channel::Pressure, "PRESSURE"),
+            (Channel::Co2, "CO2"),
+        ] {
+            let seconds_var = format!("ESS_{}_SAMPLING_RATE", name);
+            let millis_var = format!("{}_MS", seconds_var);
+            let millis = match (
+                env_override::<u64>(&seconds_var)?,
+                env_override::<u64>(&millis_var)?,
+            ) {
+                (Some(_), Some(_)) => {
+                    return Err(format!(
+                        "set only one of {} and {}",
+                        seconds_var, millis_var
+                    ))
+                }
+                (Some(seconds), None) => Some(seconds.saturating_mul(1000)),
+                (None, millis) => millis,
+            };
+            if let Some(millis) = millis {
+                self.track_override(|config| config.sensors.set_sampling_rate_ms(channel, millis))?;
+            }
+        }
+        if let Some(tick) = env_override("ESS_TICK_INTERVAL_MS")? {
+            self.track_override(|config| config.sensors.tick_interval_ms = tick)?;
+        }
+        if let Some(dither) = env_override("ESS_SAMPLING_DITHER_MS")? {
+            self.track_override(|config| config.sensors.sampling_dither_ms = dither)?;
+        }
+        if let Some(rate) = env_override("ESS_FAILURE_RATE")? {
+            self.track_override(|config| config.sensors.failure_rate = rate)?;
+        }
+        if let Some(seed) = env_override("ESS_SEED")? {
+            self.track_override(|config| config.sensors.seed = Some(seed))?;
+        }
+
+        if let Some(path) = env_override("ESS_LOG_FILE_PATH")? {
+            self.track_override(|config| config.storage.log_file_path = path)?;
+        }
+        if let Some(graph) = env_override("ESS_REAL_TIME_GRAPH")? {
+            self.track_override(|config| config.display.real_time_graph = graph)?;
+        }
+        if let Some(port) = env_override("ESS_METRICS_PORT")? {
+            self.track_override(|config| config.display.metrics_port = Some(port))?;
+        }
+
+        if let Some(threshold) = env_override("ESS_TEMPERATURE_THRESHOLD")? {
+            self.track_override(|config| config.alerts.temperature_threshold = threshold)?;
+        }
+        if let Some(threshold) = env_override("ESS_HUMIDITY_THRESHOLD")? {
+            self.track_override(|config| config.alerts.humidity_threshold = threshold)?;
+        }
+        if let Some(threshold) = env_override("ESS_LIGHT_THRESHOLD")? {
+            self.track_override(|config| config.alerts.light_threshold = threshold)?;
+        }
+        if let Some(threshold) = env_override("ESS_PRESSURE_THRESHOLD")? {
+            self.track_override(|config| config.alerts.pressure_threshold = Some(threshold))?;
+        }
+        if let Some(threshold) = env_override("ESS_CO2_THRESHOLD")? {
+            self.track_override(|config| config.alerts.co2_threshold = Some(threshold))?;
+        }
+        Ok(())
+    }
+
+    // Apply one override, remembering the file's values it replaced so that
+    // saving the settings doesn't make the override permanent
+    fn track_override(&mut self, apply: impl FnOnce(&mut Config)) -> Result<(), String> {
+        let before = toml::Value::try_from(&*self).map_err(|e| e.to_string())?;
+        apply(self);
+        let after = toml::Value::try_from(&*self).map_err(|e| e.to_string())?;
+        self.env_overrides
+            .push(EnvOverride::between(&before, &after));
+        Ok(())
+    }
+
+    // Re-read the config during a run. The new config is fully parsed and
+    // validated before anything is applied, so on any error the caller keeps
+    // running with its current settings.
+    pub fn reload(&self) -> Result<Config, Box<dyn Error>> {
+        Config::load(&self.source)
+    }
+
+    // Write the configuration to `path`. The file is only replaced once the
+    // new contents are complete, so a failed save leaves it as it was. Settings
+    // that wouldn't load again are refused rather than written.
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        self.validate()?;
+        // Keep the file's values where `ESS_*` variables overrode them, unless
+        // they were changed since
+        let mut values = toml::Value::try_from(self)?;
+        for env_override in &self.env_overrides {
+            env_override.restore(&mut values);
+        }
+        let saved: Config = values.try_into()?;
+        saved.validate()?;
+        let config_str = toml::to_string(&saved)?;
+        let temp_path = format!("{}.tmp", path);
+        fs::write(&temp_path, config_str)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    // Whether the config was fetched over HTTP(S) rather than read from a file
+    pub fn is_remote(&self) -> bool {
+        is_remote(&self.source)
+    }
+
+    pub fn parse(config_str: &str) -> Result<Self, Box<dyn Error>> {
+        let config: Config = parse_toml(config_str)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Catch settings that are invalid, or don't work together, before a run
+    // starts rather than at the first write
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sensors.tick_interval_ms == 0 {
+            return Err("tick_interval_ms must be greater than 0".to_string());
+        }
+        self.sensors.validate_sampling_rates()?;
+        self.alerts.validate(self.display.temperature_unit)?;
+        if !(0.0..=1.0).contains(&self.sensors.failure_rate) {
+            return Err(format!(
+                "failure_rate must be between 0 and 1, got {}",
+                self.sensors.failure_rate
+            ));
+        }
+        for filter in [
+            &self.sensors.temperature_filter,
+            &self.sensors.humidity_filter,
+            &self.sensors.light_filter,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            filter.validate()?;
+        }
+        self.sensors.validate_first_sample()?;
+        if self.stats.reservoir_size == Some(0) {
+            return Err("reservoir_size must be greater than 0, got 0".to_string());
+        }
+        for model in [
+            &self.sensors.temperature_model,
+            &self.sensors.humidity_model,
+            &self.sensors.light_model,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            model.validate()?;
+        }
+        for transient in [
+            &self.sensors.temperature_transient,
+            &self.sensors.humidity_transient,
+            &self.sensors.light_transient,
+        ] {
+            transient.validate()?;
+        }
+        for gate in [
+            &self.sensors.temperature_gate,
+            &self.sensors.humidity_gate,
+            &self.sensors.light_gate,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if gate.attempts == 0 {
+                return Err("a sensor gate needs at least 1 attempt".to_string());
+            }
+        }
+        let uses_system_metrics = [
+            self.sensors.temperature_source,
+            self.sensors.humidity_source,
+            self.sensors.light_source,
+        ]
+        .iter()
+        .any(|source| matches!(source, SensorSource::System { .. }));
+        if uses_system_metrics && !cfg!(feature = "system-metrics") {
+            return Err(
+                "sensor sources of type \"system\" require building with `--features system-metrics`"
+                    .to_string(),
+            );
+        }
+
+        if self.notifications.webhook_url.is_some() && !cfg!(feature = "webhook") {
+            return Err("webhook_url requires building with `--features webhook`".to_string());
+        }
+        if self.notifications.syslog && !cfg!(unix) {
+            return Err("syslog notifications are only supported on Unix".to_string());
+        }
+
+        if self.statsd.is_some() && !cfg!(feature = "statsd") {
+            return Err("[statsd] requires building with `--features statsd`".to_string());
+        }
+        if self.mqtt.is_some() && !cfg!(feature = "mqtt") {
+            return Err("[mqtt] requires building with `--features mqtt`".to_string());
+        }
+
+        if self.display.outputs.contains(&DisplayOutput::Image) && !cfg!(feature = "imaging") {
+            return Err(
+                "the \"image\" display output requires building with `--features imaging`"
+                    .to_string(),
+            );
+        }
+
+        let storage = &self.storage;
+        if storage.log_file_path.trim().is_empty() {
+            return Err(format!(
+                "log_file_path must not be empty, got {:?}",
+                storage.log_file_path
+            ));
+        }
+        if storage.max_log_size_bytes == Some(0) {
+            return Err("max_log_size_bytes must be greater than 0".to_string());
+        }
+        if storage.max_log_size_bytes.is_some() && storage.max_log_files == 0 {
+            return Err("max_log_files must be at least 1".to_string());
+        }
+        if storage.upload.is_some() {
+            if !cfg!(feature = "s3-upload") {
+                return Err(
+                    "[storage.upload] requires building with `--features s3-upload`".to_string(),
+                );
+            }
+            if storage.rotation.is_none() {
+                return Err(
+                    "[storage.upload] uploads rotated segments and needs rotation".to_string(),
+                );
+            }
+        }
+        if storage.log_format == LogFormat::Parquet {
+            if !cfg!(feature = "parquet") {
+                return Err(
+                    "log_format = \"parquet\" requires building with `--features parquet`"
+                        .to_string(),
+                );
+            }
+            if storage.rotation.is_some() {
+                return Err("rotation is not supported with log_format = \"parquet\"".to_string());
+            }
+            if storage.max_log_size_bytes.is_some() {
+                return Err(
+                    "max_log_size_bytes is not supported with log_format = \"parquet\"".to_string(),
+                );
+            }
+            if storage.timestamp_resolution == TimestampResolution::Counter {
+                // Parquet timestamps always have millisecond resolution
+                return Err(
+                    "timestamp_resolution = \"counter\" is not supported with log_format = \"parquet\""
+                        .to_string(),
+                );
+            }
+        }
+
+        if let (Some(min), Some(max)) = (self.run.min_duration_secs, self.run.max_duration_secs) {
+            if min > max {
+                return Err(format!(
+                    "run.min_duration_secs ({}) is greater than run.max_duration_secs ({})",
+                    min, max
+                ));
+            }
+        }
+
+        // Files written during a run must not overwrite each other
+        let mut outputs = vec![("log_file_path", &storage.log_file_path)];
+        if let Some(stats_output) = &self.stats_output {
+            outputs.push(("stats_output.path", &stats_output.path));
+        }
+        if self.display.outputs.contains(&DisplayOutput::Snapshot) {
+            outputs.push(("display.snapshot_path", &self.display.snapshot_path));
+        }
+        if self.display.outputs.contains(&DisplayOutput::Image) {
+            outputs.push(("display.image_path", &self.display.image_path));
+        }
+        for (i, (name, path)) in outputs.iter().enumerate() {
+            if let Some((other, _)) = outputs[..i].iter().find(|(_, other)| other == path) {
+                return Err(format!("{} and {} are both {}", other, name, path));
+            }
+        }
+        Ok(())
+    }
+
+    fn load_remote(url: &str) -> Result<Self, Box<dyn Error>> {
+        let fetched = fetch_remote_config(url).and_then(|config_str| {
+            let config = Config::parse(&config_str)?;
+            Ok((config, config_str))
+        });
+
+        match fetched {
+            Ok((config, config_str)) => {
+                if let Err(e) = fs::write(REMOTE_CONFIG_CACHE, config_str) {
+                    println!("[WARNING]: Could not cache remote config: {}", e);
+                }
+                Ok(config)
+            }
+            Err(e) => {
+                let config_str = read_config_file(REMOTE_CONFIG_CACHE)
+                    .map_err(|_| format!("Failed to load config from {}: {}", url, e))?;
+                println!(
+                    "[WARNING]: Failed to load config from {} ({}); using cached copy {}",
+                    url, e, REMOTE_CONFIG_CACHE
+                );
+                Config::parse(&config_str)
+            }
+        }
+    }
+}
+
+fn is_remote(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+#[cfg(feature = "remote-config")]
+fn fetch_remote_config(url: &str) -> Result<String, Box<dyn Error>> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(10))
+        .build();
+    Ok(agent.get(url).call()?.into_string()?)
+}
+
+#[cfg(not(feature = "remote-config"))]
+fn fetch_remote_config(url: &str) -> Result<String, Box<dyn Error>> {
+    Err(format!(
+        "cannot fetch {}: built without the `remote-config` feature",
+        url
+    )
+    .into())
+}
+
+impl AlertConfig {
+    // Load thresholds from a standalone file containing the `[alerts]` keys,
+    // with temperatures in `unit`
+    pub fn load(path: &str, unit: TemperatureUnit) -> Result<Self, Box<dyn Error>> {
+        let alerts_str = read_config_file(path)?;
+        AlertConfig::parse(&alerts_str, unit)
+    }
+
+    pub fn parse(alerts_str: &str, unit: TemperatureUnit) -> Result<Self, Box<dyn Error>> {
+        let alerts: AlertConfig = parse_toml(alerts_str)?;
+        alerts.validate(unit)?;
+        Ok(alerts)
+    }
+
+    // Channels the alerts of `sensor` are sent to
+    pub fn channels(&self, sensor: Channel) -> &[AlertChannel] {
+        match sensor {
+            Channel::Temperature => &self.temperature_alert_channels,
+            Channel::Humidity => &self.humidity_alert_channels,
+            Channel::Light => &self.light_alert_channels,
+            Channel::Pressure => &self.pressure_alert_channels,
+            Channel::Co2 => &self.co2_alert_channels,
+        }
+    }
+
+    // Whether alerts of `sensor` and `kind` are kept off every channel
+    pub fn suppresses(&self, sensor: Channel, kind: AlertKind) -> bool {
+        self.suppress.iter().any(|rule| {
+            rule.sensor == sensor && rule.kind.is_none_or(|rule_kind| rule_kind == kind)
+        })
+    }
+
+    // Thresholds must lie within what the sensors could plausibly read, so a
+    // typo such as `humidity_threshold = 650` is caught rather than never firing.
+    // Temperatures are in `unit`.
+    pub fn validate(&self, unit: TemperatureUnit) -> Result<(), String> {
+        let temperature_range = unit.from_celsius(*TEMPERATURE_RANGE.start())
+            ..=unit.from_celsius(*TEMPERATURE_RANGE.end());
+        for (name, value, range) in [
+            (
+                "temperature_threshold",
+                Some(self.temperature_threshold),
+                temperature_range.clone(),
+            ),
+            (
+                "humidity_threshold",
+                Some(self.humidity_threshold),
+                PERCENT_RANGE,
+            ),
+            ("light_threshold", Some(self.light_threshold), PERCENT_RANGE),
+            (
+                "pressure_threshold",
+                self.pressure_threshold,
+                PRESSURE_RANGE,
+            ),
+            ("co2_threshold", self.co2_threshold, CO2_RANGE),
+            ("temperature_min", self.temperature_min, temperature_range),
+            ("humidity_min", self.humidity_min, PERCENT_RANGE),
+            ("light_min", self.light_min, PERCENT_RANGE),
+        ] {
+            let Some(value) = value else { continue };
+            if !range.contains(&value) {
+                return Err(format!(
+                    "{} must be between {} and {}, got {}",
+                    name,
+                    range.start(),
+                    range.end(),
+                    value
+                ));
+            }
+        }
+        for (name, min, threshold) in [
+            (
+                "temperature_min",
+                self.temperature_min,
+                self.temperature_threshold,
+            ),
+            ("humidity_min", self.humidity_min, self.humidity_threshold),
+            ("light_min", self.light_min, self.light_threshold),
+        ] {
+            let Some(min) = min else { continue };
+            if min >= threshold {
+                return Err(format!(
+                    "{} ({}) must be below the threshold ({})",
+                    name, min, threshold
+                ));
+            }
+        }
+        if let Some(rate) = self.temperature_rate_threshold {
+            if rate.is_nan() || rate <= 0.0 {
+                return Err(format!(
+                    "temperature_rate_threshold must be greater than 0, got {}",
+                    rate
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Watches the thresholds file and reloads it whenever it changes
+pub struct ThresholdsWatcher {
+    pub path: String,
+    unit: TemperatureUnit,
+    last_modified: Option<SystemTime>,
+    checked: bool,
+}
+
+impl ThresholdsWatcher {
+    pub fn new(path: &str, unit: TemperatureUnit) -> Self {
+        ThresholdsWatcher {
+            path: path.to_string(),
+            unit,
+            last_modified: None,
+            checked: false,
+        }
+    }
+
+    // Returns the freshly loaded thresholds if the file changed since the last poll
+    pub fn poll(&mut self) -> Option<Result<AlertConfig, Box<dyn Error>>> {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if self.checked && modified == self.last_modified {
+            return None;
+        }
+        self.checked = true;
+        self.last_modified = modified;
+        Some(AlertConfig::load(&self.path, self.unit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/config.toml");
+
+    fn temp_config(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}_{}.toml", name, std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        fs::copy(EXAMPLE_CONFIG, &path).unwrap();
+        path
+    }
+
+    // The example config with `from` replaced by `to`
+    fn parse_example_with(from: &str, to: &str) -> Result<Config, Box<dyn Error>> {
+        let example = fs::read_to_string(EXAMPLE_CONFIG).unwrap();
+        assert!(example.contains(from), "{} not in the example config", from);
+        Config::parse(&example.replacen(from, to, 1))
+    }
+
+    fn validation_error(from: &str, to: &str) -> String {
+        parse_example_with(from, to).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn example_config_is_valid() {
+        let config = parse_example_with("", "").unwrap();
+        assert_eq!(config.storage.log_file_path, "sensor_logs.txt");
+    }
+
+    #[test]
+    fn zero_sampling_rates_are_rejected() {
+        assert_eq!(
+            validation_error("humidity_sampling_rate = 2", "humidity_sampling_rate = 0"),
+            "humidity_sampling_rate must be greater than 0, got 0"
+        );
+        assert_eq!(
+            validation_error("light_sampling_rate = 3", "light_sampling_rate_ms = 0"),
+            "light_sampling_rate_ms must be greater than 0, got 0"
+        );
+    }
+
+    #[test]
+    fn implausible_thresholds_are_rejected() {
+        assert_eq!(
+            validation_error("humidity_threshold = 65.0", "humidity_threshold = 650.0"),
+            "humidity_threshold must be between 0 and 100, got 650"
+        );
+        assert_eq!(
+            validation_error(
+                "temperature_threshold = 28.0",
+                "temperature_threshold = -300.0"
+            ),
+            "temperature_threshold must be between -55 and 125, got -300"
+        );
+        assert_eq!(
+            validation_error(
+                "light_threshold = 90.0",
+                "light_threshold = 90.0\nco2_threshold = -1.0"
+            ),
+            "co2_threshold must be between 0 and 10000, got -1"
+        );
+    }
+
+    #[test]
+    fn fahrenheit_thresholds_are_checked_in_fahrenheit() {
+        let fahrenheit = "real_time_graph = true\ntemperature_unit = \"fahrenheit\"";
+        let config = parse_example_with("real_time_graph = true", fahrenheit).unwrap();
+        let alerts = config.alerts.clone();
+        let hot = AlertConfig {
+            temperature_threshold: 200.0,
+            ..alerts.clone()
+        };
+        assert!(hot.validate(TemperatureUnit::Fahrenheit).is_ok());
+        assert_eq!(
+            hot.validate(TemperatureUnit::Celsius).unwrap_err(),
+            "temperature_threshold must be between -55 and 125, got 200"
+        );
+        let cold = AlertConfig {
+            temperature_min: Some(-70.0),
+            ..alerts
+        };
+        assert_eq!(
+            cold.validate(TemperatureUnit::Fahrenheit).unwrap_err(),
+            "temperature_min must be between -67 and 257, got -70"
+        );
+    }
+
+    #[test]
+    fn invalid_transients_are_rejected() {
+        let transient = |settings: &str| {
+            validation_error(
+                "[storage]",
+                &format!("[sensors.humidity_transient]\n{}\n[storage]", settings),
+            )
+        };
+        assert_eq!(
+            transient("samples = 5\nnoise = inf"),
+            "a sensor transient's offset and noise must be finite numbers"
+        );
+        assert_eq!(
+            transient("offset = nan"),
+            "a sensor transient's offset and noise must be finite numbers"
+        );
+        assert_eq!(
+            transient("noise = -1.0"),
+            "transient noise must not be negative, got -1"
+        );
+    }
+
+    #[test]
+    fn empty_reservoir_is_rejected() {
+        assert_eq!(
+            validation_error("[storage]", "[stats]\nreservoir_size = 0\n[storage]"),
+            "reservoir_size must be greater than 0, got 0"
+        );
+    }
+
+    #[test]
+    fn empty_log_file_path_is_rejected() {
+        assert_eq!(
+            validation_error(
+                "log_file_path = \"sensor_logs.txt\"",
+                "log_file_path = \" \""
+            ),
+            "log_file_path must not be empty, got \" \""
+        );
+    }
+
+    #[test]
+    fn saved_settings_reload_unchanged() {
+        let path = temp_config("config_round_trip");
+        let mut config = Config::load(&path).unwrap();
+        config
+            .sensors
+            .set_sampling_rate_ms(Channel::Temperature, 250);
+        config.alerts.humidity_threshold = 42.5;
+        config.save(&path).unwrap();
+
+        let reloaded = Config::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            reloaded.sensors.sampling_rate(Channel::Temperature),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(reloaded.alerts.humidity_threshold, 42.5);
+        assert_eq!(
+            toml::to_string(&reloaded).unwrap(),
+            toml::to_string(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn suppress_rules_match_a_sensor_or_one_rule() {
+        let alerts = AlertConfig::parse(
+            "temperature_threshold = 30.0\n\
+             humidity_threshold = 60.0\n\
+             light_threshold = 80.0\n\
+             suppress = [\"humidity\", \"temperature.low\"]\n",
+            TemperatureUnit::Celsius,
+        )
+        .unwrap();
+        assert!(alerts.suppresses(Channel::Humidity, AlertKind::High));
+        assert!(alerts.suppresses(Channel::Humidity, AlertKind::Low));
+        assert!(alerts.suppresses(Channel::Temperature, AlertKind::Low));
+        assert!(!alerts.suppresses(Channel::Temperature, AlertKind::High));
+        assert!(!alerts.suppresses(Channel::Light, AlertKind::High));
+        assert!(AlertConfig::parse(
+            "temperature_threshold = 30.0\n\
+             humidity_threshold = 60.0\n\
+             light_threshold = 80.0\n\
+             suppress = [\"humidity.rising\"]\n",
+            TemperatureUnit::Celsius,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn invalid_settings_are_not_saved() {
+        let path = temp_config("config_invalid_save");
+        let original = fs::read_to_string(&path).unwrap();
+        let mut config = Config::load(&path).unwrap();
+        config.sensors.tick_interval_ms = 0;
+        assert!(config.save(&path).is_err());
+
+        let kept = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(kept, original);
+    }
+}
@@ -0,0 +1,449 @@
+use crate::config::{
+    DisplayConfig, DisplayOutput, GraphBaseline, PartialWindow, SensorColor, SensorDisplayConfig,
+};
+#[cfg(feature = "imaging")]
+use crate::image_display::ImageDisplay;
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Result, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Where readings, graphs, alerts and statistics are shown
+pub trait Display {
+    fn show(&mut self, data: &str);
+    // `values` are the most recent ones of a window of `window_size` values
+    fn show_graph(
+        &mut self,
+        name: &str,
+        values: &[f32],
+        window_size: usize,
+        label: &SensorDisplayConfig,
+    );
+    fn show_alert(&mut self, message: &str);
+    fn show_statistics(&mut self, line: &str, label: &SensorDisplayConfig);
+
+    // The status line, one part per sensor in the sensor's color
+    fn show_readings(&mut self, parts: &[(String, Option<SensorColor>)]) {
+        let line: Vec<&str> = parts.iter().map(|(part, _)| part.as_str()).collect();
+        self.show(&line.join(", "));
+    }
+
+    // Called once everything of a frame has been shown
+    fn end_frame(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Console Display
+#[derive(Default)]
+pub struct ConsoleDisplay {
+    rate_colors: bool,
+    sensor_colors: bool,
+    pub partial_window: PartialWindow,
+}
+
+impl ConsoleDisplay {
+    pub fn new() -> Self {
+        ConsoleDisplay::default()
+    }
+}
+
+impl Display for ConsoleDisplay {
+    fn show(&mut self, data: &str) {
+        println!("[LCD Display]: {}", data);
+    }
+
+    fn show_graph(
+        &mut self,
+        name: &str,
+        values: &[f32],
+        window_size: usize,
+        label: &SensorDisplayConfig,
+    ) {
+        let Some(lines) = graph_lines(
+            values,
+            window_size,
+            self.partial_window,
+            label,
+            self.rate_colors,
+            self.sensor_colors,
+        ) else {
+            return;
+        };
+        println!(
+            "[Real-Time Graph] {}",
+            paint(name, label.color, self.sensor_colors)
+        );
+        for line in lines {
+            println!("{}", line);
+        }
+    }
+
+    fn show_alert(&mut self, message: &str) {
+        println!("[ALERT]: {}", message);
+    }
+
+    fn show_statistics(&mut self, line: &str, label: &SensorDisplayConfig) {
+        println!(
+            "[Statistics] {}",
+            paint(line, label.color, self.sensor_colors)
+        );
+    }
+
+    fn show_readings(&mut self, parts: &[(String, Option<SensorColor>)]) {
+        let line: Vec<String> = parts
+            .iter()
+            .map(|(part, color)| paint(part, *color, self.sensor_colors))
+            .collect();
+        self.show(&line.join(", "));
+    }
+}
+
+// Keeps the latest frame in a file, e.g. for a status page or `watch cat`.
+// The file is replaced as a whole so readers never see half a frame.
+pub struct SnapshotDisplay {
+    path: String,
+    partial_window: PartialWindow,
+    frame: String,
+}
+
+impl SnapshotDisplay {
+    pub fn new(path: &str, partial_window: PartialWindow) -> Self {
+        SnapshotDisplay {
+            path: path.to_string(),
+            partial_window,
+            frame: String::new(),
+        }
+    }
+
+    fn push_line(&mut self, line: &str) {
+        self.frame.push_str(line);
+        self.frame.push('\n');
+    }
+}
+
+impl Display for SnapshotDisplay {
+    fn show(&mut self, data: &str) {
+        self.push_line(&format!("[LCD Display]: {}", data));
+    }
+
+    fn show_graph(
+        &mut self,
+        name: &str,
+        values: &[f32],
+        window_size: usize,
+        label: &SensorDisplayConfig,
+    ) {
+        let Some(lines) = graph_lines(
+            values,
+            window_size,
+            self.partial_window,
+            label,
+            false,
+            false,
+        ) else {
+            return;
+        };
+        self.push_line(&format!("[Real-Time Graph] {}", name));
+        for line in lines {
+            self.push_line(&line);
+        }
+    }
+
+    fn show_alert(&mut self, message: &str) {
+        self.push_line(&format!("[ALERT]: {}", message));
+    }
+
+    fn show_statistics(&mut self, line: &str, _label: &SensorDisplayConfig) {
+        self.push_line(&format!("[Statistics] {}", line));
+    }
+
+    fn end_frame(&mut self) -> Result<()> {
+        let temp_path = format!("{}.tmp", self.path);
+        fs::write(&temp_path, &self.frame)?;
+        fs::rename(&temp_path, &self.path)?;
+        self.frame.clear();
+        Ok(())
+    }
+}
+
+// All configured displays, fed the same frames. Frames are skipped when they
+// come faster than the maximum refresh rate, or faster than the displays
+// managed to render the last one.
+#[derive(Default)]
+pub struct Displays {
+    outputs: Vec<Box<dyn Display>>,
+    min_frame_interval: Duration,
+    last_frame: Option<Instant>,
+    render_time: Duration,
+    pub dropped_frames: u64,
+}
+
+impl Displays {
+    // The displays selected by `outputs` under `[display]`, throttled as configured
+    pub fn from_config(config: &DisplayConfig) -> Result<Self> {
+        let outputs = config
+            .outputs
+            .iter()
+            .map(|output| -> Result<Box<dyn Display>> {
+                Ok(match output {
+                    DisplayOutput::Console => Box::new(ConsoleDisplay {
+                        rate_colors: config.rate_colors && colors_supported(),
+                        sensor_colors: colors_supported(),
+                        partial_window: config.partial_window,
+                    }),
+                    DisplayOutput::Snapshot => Box::new(SnapshotDisplay::new(
+                        &config.snapshot_path,
+                        config.partial_window,
+                    )),
+                    #[cfg(feature = "imaging")]
+                    DisplayOutput::Image => Box::new(ImageDisplay::new(config)?),
+                    #[cfg(not(feature = "imaging"))]
+                    DisplayOutput::Image => {
+                        return Err(io::Error::other(
+                            "the image display requires building with `--features imaging`",
+                        ))
+                    }
+                })
+            })
+            .collect::<Result<_>>()?;
+        let min_frame_interval = match (config.min_update_interval_ms, config.max_refresh_hz) {
+            (Some(ms), _) => Duration::from_millis(ms),
+            (None, 0) => Duration::ZERO,
+            (None, hz) => Duration::from_secs(1) / hz,
+        };
+        Ok(Displays {
+            outputs,
+            min_frame_interval,
+            ..Displays::default()
+        })
+    }
+
+    // Start a new frame, returning false if it should be skipped
+    pub fn begin_frame(&mut self) -> bool {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            let interval = self.min_frame_interval.max(self.render_time);
+            if now - last_frame < interval {
+                self.dropped_frames += 1;
+                return false;
+            }
+        }
+        self.last_frame = Some(now);
+        self.render_time = Duration::ZERO;
+        true
+    }
+
+    // Run `render` on every display, adding the time taken to this frame's
+    fn render(&mut self, mut render: impl FnMut(&mut dyn Display)) {
+        let start = Instant::now();
+        for output in &mut self.outputs {
+            render(output.as_mut());
+        }
+        self.render_time += start.elapsed();
+    }
+}
+
+impl Display for Displays {
+    fn show(&mut self, data: &str) {
+        self.render(|output| output.show(data));
+    }
+
+    fn show_graph(
+        &mut self,
+        name: &str,
+        values: &[f32],
+        window_size: usize,
+        label: &SensorDisplayConfig,
+    ) {
+        self.render(|output| output.show_graph(name, values, window_size, label));
+    }
+
+    fn show_alert(&mut self, message: &str) {
+        self.render(|output| output.show_alert(message));
+    }
+
+    fn show_statistics(&mut self, line: &str, label: &SensorDisplayConfig) {
+        self.render(|output| output.show_statistics(line, label));
+    }
+
+    fn show_readings(&mut self, parts: &[(String, Option<SensorColor>)]) {
+        self.render(|output| output.show_readings(parts));
+    }
+
+    fn end_frame(&mut self) -> Result<()> {
+        let start = Instant::now();
+        for output in &mut self.outputs {
+            output.end_frame()?;
+        }
+        self.render_time += start.elapsed();
+        Ok(())
+    }
+}
+
+// Lines of a bar graph of `values`, colored by rate of change if `rate_colors`
+// and otherwise in the sensor's color if `sensor_colors`. A window that isn't
+// full yet is drawn as `partial_window` says; `None` hides the graph.
+fn graph_lines(
+    values: &[f32],
+    window_size: usize,
+    partial_window: PartialWindow,
+    label: &SensorDisplayConfig,
+    rate_colors: bool,
+    sensor_colors: bool,
+) -> Option<Vec<String>> {
+    let missing = window_size.saturating_sub(values.len());
+    if missing > 0 && partial_window == PartialWindow::Hide {
+        return None;
+    }
+    // From zero a bar has one character per 2 units. Above another baseline,
+    // the bars are stretched so the largest value spans `GRAPH_WIDTH`.
+    let finite = || values.iter().copied().filter(|value| value.is_finite());
+    let baseline = match label.graph_baseline {
+        GraphBaseline::Zero => 0.0,
+        GraphBaseline::WindowMin => finite().reduce(f32::min).unwrap_or(0.0),
+        GraphBaseline::Fixed(baseline) => baseline,
+    };
+    let scale = match finite().reduce(f32::max) {
+        _ if label.graph_baseline == GraphBaseline::Zero => 0.5,
+        Some(max) if max > baseline => GRAPH_WIDTH / (max - baseline),
+        _ => 0.0,
+    };
+    let mut previous = None;
+    let lines = values.iter().map(|&value| {
+        // Values below the baseline get an empty bar
+        let length = ((value - baseline).max(0.0) * scale) as usize;
+        let mut bar = "=".repeat(length);
+        if rate_colors {
+            let change = previous.map_or(0.0, |previous: f32| (value - previous).abs());
+            bar = format!("{}{}{}", rate_color(change, label), bar, RESET);
+        } else {
+            bar = paint(&bar, label.color, sensor_colors);
+        }
+        previous = Some(value);
+        format!(
+            "{:width$.precision$} | {}",
+            value,
+            bar,
+            width = label.width,
+            precision = label.precision
+        )
+    });
+    // Rows for the values still missing, with an empty bar at the baseline
+    let padding = std::iter::repeat_n(format!("{:>width$} |", "-", width = label.width), missing);
+    Some(match partial_window {
+        PartialWindow::Pad => padding.chain(lines).collect(),
+        PartialWindow::LeftAlign => lines.chain(padding).collect(),
+        PartialWindow::Grow | PartialWindow::Hide => lines.collect(),
+    })
+}
+
+// Bar length of the largest value above a non-zero baseline
+const GRAPH_WIDTH: f32 = 50.0;
+
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const BLUE: &str = "\x1b[34m";
+const MAGENTA: &str = "\x1b[35m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+// `text` in `color`, unless colors are off or the sensor has none
+fn paint(text: &str, color: Option<SensorColor>, colors: bool) -> String {
+    match color {
+        Some(color) if colors => {
+            let code = match color {
+                SensorColor::Red => RED,
+                SensorColor::Green => GREEN,
+                SensorColor::Yellow => YELLOW,
+                SensorColor::Blue => BLUE,
+                SensorColor::Magenta => MAGENTA,
+                SensorColor::Cyan => CYAN,
+            };
+            format!("{}{}{}", code, text, RESET)
+        }
+        _ => text.to_string(),
+    }
+}
+
+// Bar color for a change of `change` since the previous sample
+fn rate_color(change: f32, label: &SensorDisplayConfig) -> &'static str {
+    if change >= label.rapid_change {
+        RED
+    } else if change >= label.moderate_change {
+        YELLOW
+    } else {
+        GREEN
+    }
+}
+
+// Colors are left out when `NO_COLOR` is set or output isn't a terminal
+pub fn colors_supported() -> bool {
+    env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal()
+}
+
+// Ask a yes/no question, defaulting to no
+pub fn confirm(question: &str) -> Result<bool> {
+    print!("{} ", question);
+    io::stdout().flush()?;
+
+    // A closed stdin answers no
+    Ok(read_input()?.is_some_and(|answer| matches!(answer.to_lowercase().as_str(), "y" | "yes")))
+}
+
+static CHAIN_INPUT: AtomicBool = AtomicBool::new(false);
+static CHAINED_INPUT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+// With chaining on, a line of several words answers the next prompts in
+// turn, e.g. `2 1 500 8` to set the temperature rate from the main menu
+pub fn set_chained_input(chain: bool) {
+    CHAIN_INPUT.store(chain, Ordering::Relaxed);
+}
+
+// Drop answers still waiting from a chained line
+pub fn clear_chained_input() {
+    CHAINED_INPUT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clear();
+}
+
+// Read a trimmed line from stdin; `None` once stdin is closed (EOF)
+pub fn read_input() -> Result<Option<String>> {
+    let mut chained = CHAINED_INPUT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(answer) = chained.pop_front() {
+        // Echo the answer so the transcript reads as if it had been typed
+        println!("{}", answer);
+        return Ok(Some(answer));
+    }
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        return Ok(None);
+    }
+    if CHAIN_INPUT.load(Ordering::Relaxed) {
+        let mut words = input.split_whitespace().map(str::to_string);
+        let first = words.next().unwrap_or_default();
+        chained.extend(words);
+        return Ok(Some(first));
+    }
+    Ok(Some(input.trim().to_string()))
+}
+
+static HEADLESS: AtomicBool = AtomicBool::new(false);
+
+// In headless mode the screen is never cleared, keeping piped output free of
+// escape codes
+pub fn set_headless(headless: bool) {
+    HEADLESS.store(headless, Ordering::Relaxed);
+}
+
+// Clear the screen
+pub fn clear_screen() {
+    if !HEADLESS.load(Ordering::Relaxed) {
+        print!("\x1B[2J\x1B[1;1H");
+    }
+}
@@ -0,0 +1,91 @@
+use crate::sensor::Reading;
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+
+// Events kept by default; older ones are dropped first
+pub const EVENT_HISTORY_CAPACITY: usize = 10_000;
+
+// Something that happened during a run
+#[derive(Debug, Clone)]
+pub enum Event {
+    Reading(Reading),
+    // `suppressed` alerts fired during maintenance, or matched a `suppress`
+    // rule, and were not shown
+    Alert { message: String, suppressed: bool },
+    MaintenanceStarted,
+    MaintenanceEnded,
+    ThresholdsReloaded,
+    ConfigReloaded,
+    Restarted,
+}
+
+#[derive(Debug, Clone)]
+pub struct TimedEvent {
+    pub timestamp: DateTime<Local>,
+    pub event: Event,
+}
+
+// Bounded in-memory history of a run's events, in the order they happened
+#[derive(Debug)]
+pub struct EventHistory {
+    capacity: usize,
+    events: VecDeque<TimedEvent>,
+    pub dropped: u64,
+}
+
+impl EventHistory {
+    pub fn new(capacity: usize) -> Self {
+        EventHistory {
+            capacity,
+            events: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    pub fn record(&mut self, event: Event) {
+        if self.capacity == 0 {
+            self.dropped += 1;
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+            self.dropped += 1;
+        }
+        self.events.push_back(TimedEvent {
+            timestamp: Local::now(),
+            event,
+        });
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TimedEvent> {
+        self.events.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn readings(&self) -> impl Iterator<Item = &Reading> {
+        self.events.iter().filter_map(|e| match &e.event {
+            Event::Reading(reading) => Some(reading),
+            _ => None,
+        })
+    }
+
+    pub fn alerts(&self) -> impl Iterator<Item = &str> {
+        self.events.iter().filter_map(|e| match &e.event {
+            Event::Alert { message, .. } => Some(message.as_str()),
+            _ => None,
+        })
+    }
+}
+
+impl Default for EventHistory {
+    fn default() -> Self {
+        EventHistory::new(EVENT_HISTORY_CAPACITY)
+    }
+}
@@ -0,0 +1,185 @@
+use crate::config::{FilterConfig, FirstSample, SensorConfig};
+use crate::stats::RollingWindow;
+
+// Per-sensor processing that carries state from one reading to the next
+pub trait Processor {
+    // Start as if the sensor had been reading `value` all along
+    fn seed(&mut self, value: f32);
+    // Process the next reading
+    fn process(&mut self, value: f32) -> f32;
+}
+
+// A stateful processor started according to the `first_sample` policy, so
+// every processor treats a sensor's first reading the same way. NaN readings
+// from a failed sensor pass through unchanged and leave the state untouched.
+pub struct Warmup<P> {
+    processor: P,
+    first_sample: FirstSample,
+    initial: Option<f32>,
+    started: bool,
+}
+
+impl<P: Processor> Warmup<P> {
+    pub fn new(processor: P, first_sample: FirstSample, initial: Option<f32>) -> Self {
+        Warmup {
+            processor,
+            first_sample,
+            initial,
+            started: false,
+        }
+    }
+
+    pub fn apply(&mut self, value: f32) -> f32 {
+        if value.is_nan() {
+            return value;
+        }
+        if !self.started {
+            self.started = true;
+            match (self.first_sample, self.initial) {
+                (FirstSample::Skip, _) => {
+                    self.processor.process(value);
+                    return f32::NAN;
+                }
+                (FirstSample::SeedFromConfig, Some(initial)) => self.processor.seed(initial),
+                _ => {}
+            }
+        }
+        self.processor.process(value)
+    }
+}
+
+// Runtime state of a sensor's digital filter
+pub enum Filter {
+    LowPass { alpha: f32, output: Option<f32> },
+    MovingAverage { window: RollingWindow },
+}
+
+impl Filter {
+    pub fn new(config: &FilterConfig) -> Self {
+        match *config {
+            FilterConfig::LowPass { alpha } => Filter::LowPass {
+                alpha,
+                output: None,
+            },
+            FilterConfig::MovingAverage { taps } => Filter::MovingAverage {
+                window: RollingWindow::new(taps),
+            },
+        }
+    }
+}
+
+impl Processor for Filter {
+    // The low-pass output starts at `value`; the moving average's window is
+    // filled with it
+    fn seed(&mut self, value: f32) {
+        match self {
+            Filter::LowPass { output, .. } => *output = Some(value),
+            Filter::MovingAverage { window } => {
+                for _ in 0..window.size() {
+                    window.push(value);
+                }
+            }
+        }
+    }
+
+    // Without a seed, the low-pass output starts at the first reading and the
+    // moving average covers the readings so far until its window fills
+    fn process(&mut self, raw: f32) -> f32 {
+        match self {
+            Filter::LowPass { alpha, output } => {
+                let next = match *output {
+                    Some(previous) => previous + *alpha * (raw - previous),
+                    None => raw,
+                };
+                *output = Some(next);
+                next
+            }
+            Filter::MovingAverage { window } => {
+                window.push(raw);
+                let values = window.values();
+                values.iter().sum::<f32>() / values.len() as f32
+            }
+        }
+    }
+}
+
+// Optional filters of the three sensors
+#[derive(Default)]
+pub struct SensorFilters {
+    temperature: Option<Warmup<Filter>>,
+    humidity: Option<Warmup<Filter>>,
+    light: Option<Warmup<Filter>>,
+}
+
+impl SensorFilters {
+    pub fn new(config: &SensorConfig) -> Self {
+        let filter = |filter: &Option<FilterConfig>, initial| {
+            filter
+                .as_ref()
+                .map(|filter| Warmup::new(Filter::new(filter), config.first_sample, initial))
+        };
+        let initial = config.initial_values;
+        SensorFilters {
+            temperature: filter(&config.temperature_filter, initial.temperature),
+            humidity: filter(&config.humidity_filter, initial.humidity),
+            light: filter(&config.light_filter, initial.light),
+        }
+    }
+
+    pub fn temperature(&mut self, raw: f32) -> f32 {
+        apply(&mut self.temperature, raw)
+    }
+
+    pub fn humidity(&mut self, raw: f32) -> f32 {
+        apply(&mut self.humidity, raw)
+    }
+
+    pub fn light(&mut self, raw: f32) -> f32 {
+        apply(&mut self.light, raw)
+    }
+}
+
+fn apply(filter: &mut Option<Warmup<Filter>>, raw: f32) -> f32 {
+    match filter {
+        Some(filter) => filter.apply(raw),
+        None => raw,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outputs(config: FilterConfig, first_sample: FirstSample, readings: &[f32]) -> Vec<f32> {
+        let mut filter = Warmup::new(Filter::new(&config), first_sample, Some(10.0));
+        readings.iter().map(|&value| filter.apply(value)).collect()
+    }
+
+    #[test]
+    fn first_sample_policy_sets_how_filters_start() {
+        let low_pass = FilterConfig::LowPass { alpha: 0.5 };
+        let average = FilterConfig::MovingAverage { taps: 4 };
+        let readings = [20.0, 24.0, 20.0];
+
+        assert_eq!(
+            outputs(low_pass.clone(), FirstSample::SeedFromFirst, &readings),
+            [20.0, 22.0, 21.0]
+        );
+        assert_eq!(
+            outputs(low_pass.clone(), FirstSample::SeedFromConfig, &readings),
+            [15.0, 19.5, 19.75]
+        );
+        let skipped = outputs(low_pass, FirstSample::Skip, &readings);
+        assert!(skipped[0].is_nan());
+        assert_eq!(skipped[1..], [22.0, 21.0]);
+
+        assert_eq!(
+            outputs(average.clone(), FirstSample::SeedFromFirst, &readings),
+            [20.0, 22.0, 64.0 / 3.0]
+        );
+        assert_eq!(
+            outputs(average, FirstSample::SeedFromConfig, &readings),
+            [12.5, 16.0, 18.5]
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use crate::sensor::Reading;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+// Fixed-size ring buffer of the most recent readings
+pub struct RingBuffer {
+    pub(crate) capacity: usize,
+    readings: VecDeque<Reading>,
+}
+
+impl RingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        // Grow on demand: the capacity comes from the config and may be huge
+        RingBuffer {
+            capacity,
+            readings: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, reading: Reading) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.readings.len() == self.capacity {
+            self.readings.pop_front();
+        }
+        self.readings.push_back(reading);
+    }
+
+    // The last `count` readings, oldest first
+    pub fn recent(&self, count: usize) -> impl Iterator<Item = &Reading> {
+        self.readings
+            .iter()
+            .skip(self.readings.len().saturating_sub(count))
+    }
+}
+
+// Ring buffer shared between the simulation loop and the HTTP server
+pub type SharedHistory = Arc<Mutex<RingBuffer>>;
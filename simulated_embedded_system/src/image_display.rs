@@ -0,0 +1,190 @@
+use crate::config::{DisplayConfig, PartialWindow, SensorColor, SensorDisplayConfig};
+use crate::display::Display;
+use plotters::prelude::*;
+use plotters::style::{register_font, FontStyle};
+use std::fs;
+use std::io::{self, Result};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 600;
+const LINE_HEIGHT: i32 = 20;
+const TEXT_HEIGHT: u32 = 200;
+
+// Renders the current readings, alerts, statistics and graph to a PNG every
+// `image_interval_secs`, for dashboards that poll a static image. The file is
+// replaced as a whole so readers never see half an image.
+pub struct ImageDisplay {
+    path: String,
+    interval: Duration,
+    last_render: Option<Instant>,
+    lines: Vec<(String, RGBColor)>,
+    graph: Graph,
+    partial_window: PartialWindow,
+}
+
+// The sensor graphed in the current frame
+#[derive(Default)]
+struct Graph {
+    name: String,
+    values: Vec<f32>,
+    color: Option<SensorColor>,
+}
+
+impl ImageDisplay {
+    pub fn new(config: &DisplayConfig) -> Result<Self> {
+        load_font(&config.image_font)?;
+        Ok(ImageDisplay {
+            path: config.image_path.clone(),
+            interval: Duration::from_secs(config.image_interval_secs),
+            last_render: None,
+            lines: Vec::new(),
+            graph: Graph::default(),
+            partial_window: config.partial_window,
+        })
+    }
+
+    fn render(&self) -> Result<()> {
+        // The bitmap backend picks the encoding from the extension
+        let temp_path = Path::new(&self.path).with_extension("tmp.png");
+        draw(&temp_path, &self.lines, &self.graph).map_err(|e| io::Error::other(e.to_string()))?;
+        fs::rename(&temp_path, &self.path)
+    }
+}
+
+impl Display for ImageDisplay {
+    fn show(&mut self, data: &str) {
+        self.lines.push((data.to_string(), BLACK));
+    }
+
+    fn show_graph(
+        &mut self,
+        name: &str,
+        values: &[f32],
+        window_size: usize,
+        label: &SensorDisplayConfig,
+    ) {
+        // The line graph can't be padded, but it can wait for a full window
+        if self.partial_window == PartialWindow::Hide && values.len() < window_size {
+            return;
+        }
+        self.graph = Graph {
+            name: name.to_string(),
+            values: values.to_vec(),
+            color: label.color,
+        };
+    }
+
+    fn show_alert(&mut self, message: &str) {
+        self.lines.push((format!("ALERT: {}", message), RED));
+    }
+
+    fn show_statistics(&mut self, line: &str, label: &SensorDisplayConfig) {
+        self.lines
+            .push((line.to_string(), label.color.map_or(BLACK, rgb)));
+    }
+
+    fn end_frame(&mut self) -> Result<()> {
+        let due = self
+            .last_render
+            .is_none_or(|last_render| last_render.elapsed() >= self.interval);
+        if due {
+            self.render()?;
+            self.last_render = Some(Instant::now());
+        }
+        self.lines.clear();
+        self.graph = Graph::default();
+        Ok(())
+    }
+}
+
+// Register the font once; plotters keeps it for the rest of the process
+fn load_font(path: &str) -> Result<()> {
+    static FONT: OnceLock<std::result::Result<(), String>> = OnceLock::new();
+    FONT.get_or_init(|| {
+        let bytes = fs::read(path).map_err(|e| format!("cannot read font {}: {}", path, e))?;
+        register_font(
+            "sans-serif",
+            FontStyle::Normal,
+            Box::leak(bytes.into_boxed_slice()),
+        )
+        .map_err(|_| format!("{} is not a TrueType font", path))
+    })
+    .clone()
+    .map_err(io::Error::other)
+}
+
+fn rgb(color: SensorColor) -> RGBColor {
+    match color {
+        SensorColor::Red => RED,
+        SensorColor::Green => GREEN,
+        SensorColor::Yellow => RGBColor(200, 160, 0),
+        SensorColor::Blue => BLUE,
+        SensorColor::Magenta => MAGENTA,
+        SensorColor::Cyan => CYAN,
+    }
+}
+
+fn draw(
+    path: &Path,
+    lines: &[(String, RGBColor)],
+    graph: &Graph,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let root = BitMapBackend::new(path, (WIDTH, HEIGHT)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let (text_area, graph_area) = root.split_vertically(TEXT_HEIGHT);
+
+    text_area.draw_text(
+        &chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        &("sans-serif", 16).into_font().color(&BLACK),
+        (10, 10),
+    )?;
+    for (i, (line, color)) in lines.iter().enumerate() {
+        text_area.draw_text(
+            line,
+            &("sans-serif", 16).into_font().color(color),
+            (10, 10 + LINE_HEIGHT * (i as i32 + 1)),
+        )?;
+    }
+
+    let points: Vec<(usize, f32)> = graph
+        .values
+        .iter()
+        .enumerate()
+        .filter(|(_, value)| value.is_finite())
+        .map(|(i, &value)| (i, value))
+        .collect();
+    if !points.is_empty() {
+        let min = points.iter().map(|&(_, v)| v).fold(f32::INFINITY, f32::min);
+        let max = points
+            .iter()
+            .map(|&(_, v)| v)
+            .fold(f32::NEG_INFINITY, f32::max);
+        // Keep a flat series off the chart's edges
+        let margin = ((max - min) * 0.1).max(1.0);
+        let mut chart = ChartBuilder::on(&graph_area)
+            .caption("Real-Time Graph", ("sans-serif", 18))
+            .margin(10)
+            .x_label_area_size(30)
+            .y_label_area_size(50)
+            .build_cartesian_2d(
+                0..graph.values.len().max(2) - 1,
+                (min - margin)..(max + margin),
+            )?;
+        chart.configure_mesh().draw()?;
+        let color = graph.color.map_or(BLUE, rgb);
+        chart
+            .draw_series(LineSeries::new(points, &color))?
+            .label(graph.name.as_str())
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+    }
+    root.present()?;
+    Ok(())
+}
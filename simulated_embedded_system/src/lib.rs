@@ -0,0 +1,37 @@
+// Simulated embedded system: virtual sensors, data logging, console display,
+// alerts, and the simulation loop tying them together.
+
+pub mod alerts;
+pub mod analysis;
+pub mod baseline;
+pub mod bus;
+pub mod clock;
+pub mod config;
+pub mod display;
+pub mod events;
+pub mod filter;
+pub mod history;
+#[cfg(feature = "imaging")]
+pub mod image_display;
+pub mod logger;
+pub mod maintenance;
+pub mod metrics;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod notify;
+#[cfg(feature = "parquet")]
+pub mod parquet_log;
+pub mod replay;
+pub mod scenario;
+pub mod sensor;
+pub mod server;
+pub mod signals;
+pub mod simulation;
+pub mod stats;
+pub mod stats_output;
+#[cfg(feature = "statsd")]
+pub mod statsd;
+#[cfg(feature = "system-metrics")]
+pub mod system_metrics;
+#[cfg(feature = "s3-upload")]
+pub mod upload;
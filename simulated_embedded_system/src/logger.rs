@@ -0,0 +1,1057 @@
+use crate::alerts::{Alert, AlertKind};
+use crate::bus::Channel;
+use crate::clock::{Clock, SystemClock};
+use crate::config::{
+    Config, FlushPolicy, LogFormat, LogLock, LogMode, NanRepresentation, Rotation,
+    TimestampResolution,
+};
+use crate::display::confirm;
+#[cfg(feature = "parquet")]
+use crate::parquet_log::ParquetWriter;
+use crate::sensor::Reading;
+use crate::simulation::RunOptions;
+#[cfg(feature = "s3-upload")]
+use crate::upload::Uploader;
+use chrono::{DateTime, Local, NaiveDateTime};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, BufWriter, Result, Write};
+use std::path::Path;
+use std::time::Duration;
+
+// Timestamp format of each log record
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// Timestamp format with `timestamp_resolution = "milliseconds"`
+pub const TIMESTAMP_FORMAT_MS: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+// Second column of alert records, in place of the readings
+pub const ALERT_FLAG: &str = "ALERT";
+
+// Second column of the record of a sensor read that dropped out
+pub const DROPOUT_FLAG: &str = "DROPOUT";
+
+// Second column of the record that starts each run, followed by the run ID
+pub const RUN_FLAG: &str = "RUN";
+
+// Trailing column of rows recorded during maintenance mode
+pub const MAINTENANCE_FLAG: &str = "maintenance";
+
+// File Logger
+pub struct FileLogger {
+    file_path: String,
+    rotation: Option<Rotation>,
+    // Size limit of a log file and how many rotated files to keep
+    size_rotation: Option<(u64, u32)>,
+    flush_policy: FlushPolicy,
+    log_lock: LogLock,
+    pub(crate) nan_representation: NanRepresentation,
+    log_raw_values: bool,
+    format: LogFormat,
+    // Header row of a CSV log, written at the top of each new file
+    csv_header: Option<String>,
+    // Never `Auto`; that is resolved when the logger is created
+    timestamp_resolution: TimestampResolution,
+    // Last timestamp written and how many records have carried it
+    last_timestamp: Option<(String, u64)>,
+    // Open file and the path it was opened at
+    writer: Option<(String, BufWriter<File>)>,
+    // `alert_log_path` and the file, opened for appending
+    alert_log: Option<(String, BufWriter<File>)>,
+    // Source of the records' timestamps
+    clock: Box<dyn Clock>,
+    failures: WriteFailures,
+    alert_log_failures: WriteFailures,
+    pub(crate) records: u64,
+    pub(crate) dropped_records: u64,
+    #[cfg(feature = "parquet")]
+    parquet: Option<ParquetWriter>,
+    #[cfg(feature = "s3-upload")]
+    uploader: Option<Uploader>,
+}
+
+impl FileLogger {
+    pub fn new(file_path: &str) -> Self {
+        FileLogger {
+            file_path: file_path.to_string(),
+            rotation: None,
+            size_rotation: None,
+            flush_policy: FlushPolicy::default(),
+            log_lock: LogLock::Off,
+            nan_representation: NanRepresentation::default(),
+            log_raw_values: false,
+            format: LogFormat::Text,
+            csv_header: None,
+            timestamp_resolution: TimestampResolution::Seconds,
+            last_timestamp: None,
+            writer: None,
+            alert_log: None,
+            clock: Box::new(SystemClock),
+            failures: WriteFailures::default(),
+            alert_log_failures: WriteFailures::default(),
+            records: 0,
+            dropped_records: 0,
+            #[cfg(feature = "parquet")]
+            parquet: None,
+            #[cfg(feature = "s3-upload")]
+            uploader: None,
+        }
+    }
+
+    // Timestamp the records with `clock` rather than the system time
+    pub fn with_clock(self, clock: impl Clock + 'static) -> Self {
+        FileLogger {
+            clock: Box::new(clock),
+            ..self
+        }
+    }
+
+    // Set up the logger for a new run. In truncate mode existing data is only
+    // overwritten after confirmation or with --force; `None` means the user declined.
+    pub fn create(
+        config: &Config,
+        options: &RunOptions,
+    ) -> std::result::Result<Option<Self>, Box<dyn Error>> {
+        let storage = &config.storage;
+        let timestamp_resolution = match storage.timestamp_resolution {
+            TimestampResolution::Auto
+                if config.sensors.min_sampling_interval() < Duration::from_secs(1) =>
+            {
+                TimestampResolution::Milliseconds
+            }
+            TimestampResolution::Auto => TimestampResolution::Seconds,
+            resolution => resolution,
+        };
+        let mut logger = FileLogger {
+            rotation: storage.rotation,
+            size_rotation: storage
+                .max_log_size_bytes
+                .map(|max_size| (max_size, storage.max_log_files)),
+            flush_policy: storage.flush_policy,
+            log_lock: storage.log_lock,
+            nan_representation: storage.nan_representation,
+            log_raw_values: storage.log_raw_values,
+            format: storage.log_format,
+            csv_header: (storage.log_format == LogFormat::Csv)
+                .then(|| csv_header(config, storage.log_raw_values)),
+            timestamp_resolution,
+            #[cfg(feature = "s3-upload")]
+            uploader: storage.upload.as_ref().map(Uploader::new),
+            ..FileLogger::new(&storage.log_file_path)
+        };
+        let now = logger.clock.now();
+        let path = &logger.current_path(now);
+        let has_data = fs::metadata(path).map(|m| m.len() > 0).unwrap_or(false);
+        if storage.log_format == LogFormat::Parquet
+            && storage.log_mode == LogMode::Append
+            && has_data
+        {
+            return Err(format!(
+                "Parquet log file {} cannot be appended to; set log_mode = \"truncate\" or choose a new log_file_path",
+                path
+            )
+            .into());
+        }
+        // Lock a text log before truncating it, so another instance's log is
+        // left alone
+        let file = match storage.log_format {
+            LogFormat::Text | LogFormat::Csv | LogFormat::Json => {
+                Some(open_log(path, storage.log_lock)?)
+            }
+            LogFormat::Parquet => None,
+        };
+        if storage.log_mode == LogMode::Truncate {
+            if has_data && !options.force {
+                if !options.interactive {
+                    return Err(format!(
+                        "Refusing to overwrite existing log file {} (use --force)",
+                        path
+                    )
+                    .into());
+                }
+                if !confirm(&format!(
+                    "Log file {} already contains data. Overwrite it? [y/N]",
+                    path
+                ))? {
+                    return Ok(None);
+                }
+            }
+            match &file {
+                Some(file) => file.set_len(0)?,
+                None => {
+                    File::create(path)?;
+                }
+            }
+        }
+        if let Some(file) = file {
+            let writer = start_log(file, logger.csv_header.as_deref())?;
+            logger.writer = Some((path.clone(), writer));
+        }
+        if let Some(alert_log_path) = &storage.alert_log_path {
+            let file = OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(alert_log_path)
+                .map_err(|e| format!("Could not open alert log {}: {}", alert_log_path, e))?;
+            logger.alert_log = Some((alert_log_path.clone(), BufWriter::new(file)));
+        }
+
+        match storage.log_format {
+            LogFormat::Text | LogFormat::Csv | LogFormat::Json => Ok(Some(logger)),
+            #[cfg(feature = "parquet")]
+            LogFormat::Parquet => Ok(Some(FileLogger {
+                parquet: Some(ParquetWriter::create(
+                    path,
+                    storage.nan_representation,
+                    storage.log_raw_values,
+                )?),
+                ..logger
+            })),
+            #[cfg(not(feature = "parquet"))]
+            LogFormat::Parquet => {
+                Err("Parquet logs require building with `--features parquet`".into())
+            }
+        }
+    }
+
+    // Log a full set of readings in the configured format. The unfiltered
+    // `raw` readings follow them with `log_raw_values = true`.
+    pub fn log_reading(
+        &mut self,
+        reading: &Reading,
+        raw: &Reading,
+        maintenance: bool,
+    ) -> Result<()> {
+        // Parquet writes fail like the text formats': the rows are counted
+        // as lost and the run goes on
+        #[cfg(feature = "parquet")]
+        if let Some(parquet) = self.parquet.as_mut() {
+            self.records += 1;
+            if self.failures.disk_full {
+                self.dropped_records += 1;
+                return Ok(());
+            }
+            let rows = parquet.buffered_rows() as u64 + 1;
+            let result = parquet.write(self.clock.now().naive_local(), reading, raw, maintenance);
+            if self.failures.dropped(result, &self.file_path) {
+                self.dropped_records += rows;
+            }
+            return Ok(());
+        }
+
+        if self.format == LogFormat::Json {
+            let record = JsonReading {
+                temperature: json_value(reading.temperature),
+                humidity: json_value(reading.humidity),
+                light: json_value(reading.light),
+                pressure: reading.pressure.map(json_value),
+                co2: reading.co2.map(json_value),
+                raw: self.log_raw_values.then(|| JsonRaw {
+                    temperature: json_value(raw.temperature),
+                    humidity: json_value(raw.humidity),
+                    light: json_value(raw.light),
+                }),
+                maintenance,
+            };
+            return self.log(&serde_json::to_string(&record)?);
+        }
+
+        let nan = self.nan_representation;
+        let mut fields = vec![
+            format_value(reading.temperature, 2, nan),
+            format_value(reading.humidity, 2, nan),
+            format_value(reading.light, 2, nan),
+        ];
+        // Optional sensors follow the core readings when configured
+        for value in [reading.pressure, reading.co2].into_iter().flatten() {
+            fields.push(format_value(value, 2, nan));
+        }
+        if self.log_raw_values {
+            for value in [raw.temperature, raw.humidity, raw.light] {
+                fields.push(format_value(value, 2, nan));
+            }
+        }
+        // CSV rows keep the columns of the header, with an empty maintenance
+        // column outside maintenance mode
+        if maintenance {
+            fields.push(MAINTENANCE_FLAG.to_string());
+        } else if self.csv_header.is_some() {
+            fields.push(String::new());
+        }
+        let separator = if self.format == LogFormat::Csv {
+            ","
+        } else {
+            ", "
+        };
+        self.log(&fields.join(separator))
+    }
+
+    // Mark the start of a run so its records can be matched to its summary,
+    // followed by the run's tags as `key=value` columns. Parquet logs store
+    // the run ID and tags in the file metadata instead.
+    pub fn log_run_start(&mut self, run_id: &str, tags: &BTreeMap<String, String>) -> Result<()> {
+        #[cfg(feature = "parquet")]
+        if let Some(parquet) = self.parquet.as_mut() {
+            parquet.set_run_metadata(run_id, tags);
+            return Ok(());
+        }
+        match self.format {
+            LogFormat::Csv => return Ok(()), // CSV logs only hold readings
+            LogFormat::Json => {
+                let record = serde_json::json!({ "run_id": run_id, "tags": tags });
+                return self.log_important(&record.to_string());
+            }
+            _ => {}
+        }
+        let mut record = format!("{}, {}", RUN_FLAG, run_id);
+        for (key, value) in tags {
+            record.push_str(&format!(", {}={}", key, value));
+        }
+        self.log_important(&record)
+    }
+
+    // Log an alert to the text log, flushing it to disk right away
+    pub fn log_alert(&mut self, message: &str) -> Result<()> {
+        #[cfg(feature = "parquet")]
+        if self.parquet.is_some() {
+            return Ok(()); // Parquet logs only hold readings
+        }
+        match self.format {
+            LogFormat::Csv => Ok(()), // CSV logs only hold readings
+            LogFormat::Json => {
+                let record = serde_json::json!({ "alert": message });
+                self.log_important(&record.to_string())
+            }
+            _ => self.log_important(&format!("{}, {}", ALERT_FLAG, message)),
+        }
+    }
+
+    // Record that a read of the sensor `name` dropped out
+    pub fn log_dropout(&mut self, name: &str) -> Result<()> {
+        #[cfg(feature = "parquet")]
+        if self.parquet.is_some() {
+            return Ok(()); // Parquet logs only hold readings
+        }
+        let message = format!("{} read failed", name);
+        match self.format {
+            LogFormat::Csv => Ok(()), // CSV logs only hold readings
+            LogFormat::Json => {
+                let record = serde_json::json!({ "dropout": message });
+                self.log(&record.to_string())
+            }
+            _ => self.log(&format!("{}, {}", DROPOUT_FLAG, message)),
+        }
+    }
+
+    // Append an alert to the alert log, if there is one, as a JSON object with
+    // its time, sensor, category ("high" or "low"), value and message. Each
+    // record is flushed right away, and failed writes are handled like the
+    // main log's.
+    pub fn log_to_alert_log(&mut self, alert: &Alert) -> Result<()> {
+        let now = self.clock.now();
+        let Some((path, writer)) = self.alert_log.as_mut() else {
+            return Ok(());
+        };
+        let timestamp = match self.timestamp_resolution {
+            TimestampResolution::Milliseconds => now.format(TIMESTAMP_FORMAT_MS),
+            _ => now.format(TIMESTAMP_FORMAT),
+        };
+        let record = JsonAlert {
+            timestamp: timestamp.to_string(),
+            sensor: alert.sensor,
+            category: alert.kind,
+            value: json_value(alert.value),
+            message: &alert.message,
+        };
+        let line = serde_json::to_string(&record)?;
+        self.records += 1;
+        if self.alert_log_failures.disk_full {
+            self.dropped_records += 1;
+            return Ok(());
+        }
+        let result = writeln!(writer, "{}", line).and_then(|()| writer.flush());
+        if self.alert_log_failures.dropped(result, path) {
+            self.dropped_records += 1;
+        }
+        Ok(())
+    }
+
+    // Complete the log file at the end of a run
+    pub fn finish(mut self) -> Result<()> {
+        #[cfg(feature = "s3-upload")]
+        if let Some(uploader) = self.uploader.take() {
+            uploader.finish();
+        }
+        #[cfg(feature = "parquet")]
+        if let Some(parquet) = self.parquet.take() {
+            return parquet.finish();
+        }
+        match self.writer.as_mut() {
+            Some((_, writer)) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    // Write a header line, unless the current file already has data
+    pub fn write_header(&mut self, header: &str) -> Result<()> {
+        let now = self.clock.now();
+        let path = self.current_path(now);
+        if fs::metadata(&path).map(|m| m.len() > 0).unwrap_or(false) {
+            return Ok(());
+        }
+        let mut file = OpenOptions::new().append(true).create(true).open(&path)?;
+        writeln!(file, "{}", header)
+    }
+
+    // Log an ordinary record, buffered unless `flush_policy = "always"`
+    pub fn log(&mut self, data: &str) -> Result<()> {
+        self.write(data, false)
+    }
+
+    // Log a record that must not be lost, flushing it immediately
+    pub fn log_important(&mut self, data: &str) -> Result<()> {
+        self.write(data, true)
+    }
+
+    fn write(&mut self, data: &str, important: bool) -> Result<()> {
+        self.records += 1;
+        // Once the disk is full, stop writing and only count what is lost
+        if self.failures.disk_full {
+            self.dropped_records += 1;
+            return Ok(());
+        }
+        let result = self.write_record(data, important);
+        if self.failures.dropped(result, &self.file_path) {
+            self.dropped_records += 1;
+        }
+        Ok(())
+    }
+
+    fn write_record(&mut self, data: &str, important: bool) -> Result<()> {
+        let now = self.clock.now();
+        let path = self.current_path(now);
+        let timestamp = match self.timestamp_resolution {
+            TimestampResolution::Milliseconds => now.format(TIMESTAMP_FORMAT_MS).to_string(),
+            _ => now.format(TIMESTAMP_FORMAT).to_string(),
+        };
+        let timestamp = match (self.timestamp_resolution, &mut self.last_timestamp) {
+            (TimestampResolution::Counter, Some((last, count))) if *last == timestamp => {
+                *count += 1;
+                format!("{} #{}", timestamp, count)
+            }
+            (_, last_timestamp) => {
+                *last_timestamp = Some((timestamp.clone(), 1));
+                timestamp
+            }
+        };
+        let line = format_record(self.format, &timestamp, data);
+
+        // Move a file that the record would grow past the size limit aside
+        if let Some((max_size, max_files)) = self.size_rotation {
+            let size = match &self.writer {
+                Some((open_path, writer)) if *open_path == path => {
+                    writer.get_ref().metadata()?.len() + writer.buffer().len() as u64
+                }
+                _ => fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+            };
+            if size > 0 && size + line.len() as u64 + 1 > max_size {
+                if let Some((_, mut writer)) = self.writer.take() {
+                    writer.flush()?;
+                }
+                rotate_by_size(&path, max_files)?;
+            }
+        }
+
+        let writer = match &mut self.writer {
+            Some((open_path, writer)) if *open_path == path => writer,
+            current => {
+                // First record, or rotation moved on to a new file
+                if let Some((_closed_path, writer)) = current {
+                    writer.flush()?;
+                    #[cfg(feature = "s3-upload")]
+                    if let Some(uploader) = self.uploader.as_mut() {
+                        uploader.upload(_closed_path);
+                    }
+                }
+                let file = open_log(&path, self.log_lock)?;
+                let writer = start_log(file, self.csv_header.as_deref())?;
+                &mut current.insert((path, writer)).1
+            }
+        };
+
+        if self.log_lock == LogLock::Serialize {
+            // Waits while another instance is writing its record
+            writer.get_ref().lock()?;
+            let written = writeln!(writer, "{}", line).and_then(|_| writer.flush());
+            writer.get_ref().unlock()?;
+            return written;
+        }
+        writeln!(writer, "{}", line)?;
+        if important || self.flush_policy == FlushPolicy::Always {
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    // File for records written at `now`. With rotation the period is added
+    // before the extension, e.g. `sensor_logs.2024-05-01-15.txt` for hourly files.
+    fn current_path(&self, now: DateTime<Local>) -> String {
+        let period = match self.rotation {
+            Some(Rotation::Hourly) => now.format("%Y-%m-%d-%H"),
+            Some(Rotation::Daily) => now.format("%Y-%m-%d"),
+            None => return self.file_path.clone(),
+        };
+        with_suffix(&self.file_path, &period.to_string())
+    }
+}
+
+// `path` with `suffix` added before the extension
+fn with_suffix(path: &str, suffix: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, suffix, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, suffix),
+    };
+    path.with_file_name(file_name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Rename a full log to `.1`, e.g. `sensor_logs.1.txt`, shifting the older
+// ones up a number and dropping the oldest beyond `max_files`
+fn rotate_by_size(path: &str, max_files: u32) -> Result<()> {
+    let numbered = |n: u32| with_suffix(path, &n.to_string());
+    match fs::remove_file(numbered(max_files)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    for n in (1..max_files).rev() {
+        match fs::rename(numbered(n), numbered(n + 1)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    fs::rename(path, numbered(1))
+}
+
+// Open a text log for appending. With `log_lock = "fail"` the file stays
+// locked while it is open, and a file another process holds is an error.
+fn open_log(path: &str, log_lock: LogLock) -> Result<File> {
+    let file = OpenOptions::new().append(true).create(true).open(path)?;
+    if log_lock == LogLock::Fail {
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::ResourceBusy,
+                    format!(
+                    "log file {} is in use by another instance (see `log_lock` under [storage])",
+                    path
+                ),
+                ))
+            }
+            Err(TryLockError::Error(e)) => return Err(e),
+        }
+    }
+    Ok(file)
+}
+
+// Line for a record written at `timestamp`. JSON records are objects, and
+// the timestamp becomes their first field.
+fn format_record(format: LogFormat, timestamp: &str, data: &str) -> String {
+    match format {
+        LogFormat::Csv => format!("{},{}", timestamp, data),
+        LogFormat::Json => {
+            let timestamp = serde_json::Value::from(timestamp);
+            match data.strip_prefix('{') {
+                Some("}") => format!("{{\"timestamp\":{}}}", timestamp),
+                Some(fields) => format!("{{\"timestamp\":{},{}", timestamp, fields),
+                None => data.to_string(),
+            }
+        }
+        _ => format!("{}, {}", timestamp, data),
+    }
+}
+
+// A reading in a JSON Lines log. Values that are NaN, such as from a failed
+// sensor, are null; the optional sensors are left out unless configured.
+#[derive(Serialize)]
+struct JsonReading {
+    temperature: Option<f32>,
+    humidity: Option<f32>,
+    light: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pressure: Option<Option<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    co2: Option<Option<f32>>,
+    // Unfiltered readings with `log_raw_values = true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw: Option<JsonRaw>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    maintenance: bool,
+}
+
+#[derive(Serialize)]
+struct JsonRaw {
+    temperature: Option<f32>,
+    humidity: Option<f32>,
+    light: Option<f32>,
+}
+
+// A record in the alert log
+#[derive(Serialize)]
+struct JsonAlert<'a> {
+    timestamp: String,
+    sensor: Channel,
+    category: AlertKind,
+    value: Option<f32>,
+    message: &'a str,
+}
+
+// Value of a JSON field, rounded to two decimals like the text log
+fn json_value(value: f32) -> Option<f32> {
+    value.is_finite().then(|| (value * 100.0).round() / 100.0)
+}
+
+// How writes to a log file are failing. A full disk stops its writes for the
+// rest of the run; any other failure may be transient, so the next record
+// tries again, and it is reported when writes start failing, not per record.
+#[derive(Default)]
+struct WriteFailures {
+    disk_full: bool,
+    // Writes are failing for another reason, e.g. the file lost its permissions
+    failing: bool,
+}
+
+impl WriteFailures {
+    // Whether a write to `path` with this result lost its record, warning
+    // about a new failure
+    fn dropped(&mut self, result: Result<()>, path: &str) -> bool {
+        match result {
+            Err(e) if e.kind() == io::ErrorKind::StorageFull => {
+                self.disk_full = true;
+                println!(
+                    "[WARNING]: Disk full while writing to {}. Continuing in memory-only mode.",
+                    path
+                );
+                true
+            }
+            Err(e) => {
+                if !self.failing {
+                    self.failing = true;
+                    println!(
+                        "[WARNING]: Could not write to {}: {}. Records are dropped until writing works again.",
+                        path, e
+                    );
+                }
+                true
+            }
+            Ok(()) => {
+                self.failing = false;
+                false
+            }
+        }
+    }
+}
+
+// Header row of a CSV log, naming the columns `log_reading` writes. The
+// optional sensors get a column whenever they exist, with either rate key.
+fn csv_header(config: &Config, log_raw_values: bool) -> String {
+    let mut columns = vec!["timestamp", "temperature", "humidity", "light"];
+    if config.sensors.sampling_rate(Channel::Pressure).is_some() {
+        columns.push("pressure");
+    }
+    if config.sensors.sampling_rate(Channel::Co2).is_some() {
+        columns.push("co2");
+    }
+    if log_raw_values {
+        columns.extend(["raw_temperature", "raw_humidity", "raw_light"]);
+    }
+    columns.push(MAINTENANCE_FLAG);
+    columns.join(",")
+}
+
+// Buffer an opened log, starting a new or empty CSV log with its header.
+// Appending to a file that already has data leaves its header alone.
+fn start_log(file: File, csv_header: Option<&str>) -> Result<BufWriter<File>> {
+    let mut writer = BufWriter::new(file);
+    if let Some(header) = csv_header {
+        if writer.get_ref().metadata()?.len() == 0 {
+            writeln!(writer, "{}", header)?;
+            writer.flush()?;
+        }
+    }
+    Ok(writer)
+}
+
+// Parse a log timestamp written at any `timestamp_resolution`. The ` #N`
+// counter only orders rows within a second and is dropped.
+pub fn parse_log_timestamp(field: &str) -> Option<NaiveDateTime> {
+    let timestamp = field
+        .split_once(" #")
+        .map_or(field, |(timestamp, _)| timestamp);
+    // `%.f` also accepts timestamps without a fraction
+    NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S%.f").ok()
+}
+
+// Format a logged value, writing NaN and infinite values as configured
+pub fn format_value(value: f32, precision: usize, nan: NanRepresentation) -> String {
+    if value.is_finite() {
+        return format!("{:.*}", precision, value);
+    }
+    match nan {
+        NanRepresentation::Nan => "NaN".to_string(),
+        NanRepresentation::Empty => String::new(),
+        NanRepresentation::Sentinel(sentinel) => sentinel.to_string(),
+    }
+}
+
+// Parse a logged value, reading the configured representation back as NaN
+pub fn parse_value(field: &str, nan: NanRepresentation) -> Option<f32> {
+    match nan {
+        NanRepresentation::Empty if field.is_empty() => Some(f32::NAN),
+        _ => {
+            let value: f32 = field.parse().ok()?;
+            match nan {
+                NanRepresentation::Sentinel(sentinel) if value == sentinel => Some(f32::NAN),
+                _ => Some(value),
+            }
+        }
+    }
+}
+
+// Parse a `timestamp, temperature, humidity, light` log line written with
+// the given NaN representation
+pub fn parse_log_line(line: &str, nan: NanRepresentation) -> Option<(NaiveDateTime, Reading)> {
+    let mut fields = line.split(',').map(str::trim);
+    let timestamp = parse_log_timestamp(fields.next()?)?;
+    let reading = Reading {
+        temperature: parse_value(fields.next()?, nan)?,
+        humidity: parse_value(fields.next()?, nan)?,
+        light: parse_value(fields.next()?, nan)?,
+        pressure: None,
+        co2: None,
+    };
+    Some((timestamp, reading))
+}
+
+// Whether a log line was recorded during maintenance mode
+pub fn is_maintenance_line(line: &str) -> bool {
+    !is_alert_line(line) && line.rsplit(',').next().map(str::trim) == Some(MAINTENANCE_FLAG)
+}
+
+// Whether a log line records an alert rather than readings
+pub fn is_alert_line(line: &str) -> bool {
+    line.split(',').map(str::trim).nth(1) == Some(ALERT_FLAG)
+}
+
+// Whether a log line records a dropped-out read rather than readings
+pub fn is_dropout_line(line: &str) -> bool {
+    line.split(',').map(str::trim).nth(1) == Some(DROPOUT_FLAG)
+}
+
+// Whether a log line marks the start of a run rather than readings
+pub fn is_run_line(line: &str) -> bool {
+    line.split(',').map(str::trim).nth(1) == Some(RUN_FLAG)
+}
+
+// Run ID and tags of a run start line
+pub fn parse_run_line(line: &str) -> Option<(String, BTreeMap<String, String>)> {
+    if !is_run_line(line) {
+        return None;
+    }
+    let mut fields = line.split(',').map(str::trim).skip(2);
+    let run_id = fields.next()?.to_string();
+    let tags = fields
+        .filter_map(|tag| tag.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    Some((run_id, tags))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SteppedClock;
+    use chrono::TimeZone;
+
+    #[test]
+    fn csv_rows_have_a_maintenance_column() {
+        let path = std::env::temp_dir().join(format!("maintenance_{}.csv", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let _ = fs::remove_file(&path);
+        let config = Config::parse(&format!(
+            "[sensors]
+             temperature_sampling_rate = 1
+             humidity_sampling_rate = 1
+             light_sampling_rate = 1
+             [storage]
+             log_file_path = {:?}
+             log_format = \"csv\"
+             [display]
+             real_time_graph = false
+             [alerts]
+             temperature_threshold = 30.0
+             humidity_threshold = 60.0
+             light_threshold = 80.0",
+            path
+        ))
+        .unwrap();
+        let mut logger = FileLogger {
+            format: LogFormat::Csv,
+            csv_header: Some(csv_header(&config, false)),
+            ..FileLogger::new(&path)
+        };
+        let reading = Reading {
+            temperature: 21.5,
+            humidity: 45.0,
+            light: 80.0,
+            pressure: None,
+            co2: None,
+        };
+        logger.log_reading(&reading, &reading, false).unwrap();
+        logger.log_reading(&reading, &reading, true).unwrap();
+        logger.finish().unwrap();
+
+        let logged = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let lines: Vec<_> = logged.lines().collect();
+        assert_eq!(lines[0], "timestamp,temperature,humidity,light,maintenance");
+        assert!(lines[1].ends_with(",21.50,45.00,80.00,"), "{}", lines[1]);
+        assert!(!is_maintenance_line(lines[1]));
+        assert!(
+            lines[2].ends_with(",21.50,45.00,80.00,maintenance"),
+            "{}",
+            lines[2]
+        );
+        assert!(is_maintenance_line(lines[2]));
+    }
+
+    #[test]
+    fn csv_header_lists_sensors_with_millisecond_rates() {
+        let config = Config::parse(
+            "[sensors]
+             temperature_sampling_rate = 1
+             humidity_sampling_rate = 1
+             light_sampling_rate = 1
+             pressure_sampling_rate_ms = 500
+             co2_sampling_rate_ms = 2000
+             [storage]
+             log_file_path = \"sensor_logs.csv\"
+             [display]
+             real_time_graph = false
+             [alerts]
+             temperature_threshold = 30.0
+             humidity_threshold = 60.0
+             light_threshold = 80.0",
+        )
+        .unwrap();
+        assert_eq!(
+            csv_header(&config, false),
+            "timestamp,temperature,humidity,light,pressure,co2,maintenance"
+        );
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        let path = std::env::temp_dir().join(format!("json_lines_{}.jsonl", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let _ = fs::remove_file(&path);
+        let mut logger = FileLogger {
+            format: LogFormat::Json,
+            ..FileLogger::new(&path)
+        };
+        let readings = [
+            Reading {
+                temperature: 21.5,
+                humidity: 45.25,
+                light: 80.0,
+                pressure: None,
+                co2: None,
+            },
+            Reading {
+                temperature: f32::NAN,
+                humidity: 50.75,
+                light: 12.5,
+                pressure: Some(1013.25),
+                co2: Some(f32::NAN),
+            },
+        ];
+        for reading in &readings {
+            logger.log_reading(reading, reading, false).unwrap();
+        }
+        logger.finish().unwrap();
+
+        let logged = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let lines: Vec<serde_json::Value> = logged
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), readings.len());
+
+        for (line, reading) in lines.iter().zip(&readings) {
+            let timestamp = line["timestamp"].as_str().unwrap();
+            assert!(parse_log_timestamp(timestamp).is_some());
+            for (field, value) in [
+                ("temperature", Some(reading.temperature)),
+                ("humidity", Some(reading.humidity)),
+                ("light", Some(reading.light)),
+                ("pressure", reading.pressure),
+                ("co2", reading.co2),
+            ] {
+                match value {
+                    None => assert!(line.get(field).is_none(), "{} was logged", field),
+                    Some(value) if value.is_nan() => assert!(line[field].is_null()),
+                    Some(value) => assert_eq!(line[field].as_f64(), Some(value as f64)),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stepped_clock_gives_reproducible_logs() {
+        let path = std::env::temp_dir().join(format!("stepped_clock_{}.txt", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let _ = fs::remove_file(&path);
+        let start = Local.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let mut logger = FileLogger::new(&path)
+            .with_clock(SteppedClock::new(start, chrono::Duration::seconds(1)));
+        let reading = Reading {
+            temperature: 21.5,
+            humidity: 45.25,
+            light: 80.0,
+            pressure: None,
+            co2: None,
+        };
+        logger.log_run_start("run-1", &BTreeMap::new()).unwrap();
+        logger.log_reading(&reading, &reading, false).unwrap();
+        logger
+            .log_alert("Temperature exceeded threshold: 21.50°C")
+            .unwrap();
+        logger.finish().unwrap();
+
+        let logged = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            logged,
+            "2024-03-01 12:00:00, RUN, run-1\n\
+             2024-03-01 12:00:01, 21.50, 45.25, 80.00\n\
+             2024-03-01 12:00:02, ALERT, Temperature exceeded threshold: 21.50°C\n"
+        );
+    }
+
+    #[test]
+    fn failed_writes_are_counted_and_logging_goes_on() {
+        let dir = std::env::temp_dir().join(format!("failed_writes_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        let path = dir.join("sensor.txt").to_string_lossy().into_owned();
+        // The second hour's log can't be opened: a directory is in its place.
+        // Unlike a read-only file, that also holds when the tests run as root.
+        fs::create_dir(dir.join("sensor.2024-03-01-13.txt")).unwrap();
+        let start = Local.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let mut logger = FileLogger {
+            rotation: Some(Rotation::Hourly),
+            ..FileLogger::new(&path)
+        }
+        .with_clock(SteppedClock::new(start, chrono::Duration::hours(1)));
+        let reading = Reading {
+            temperature: 21.5,
+            humidity: 45.25,
+            light: 80.0,
+            pressure: None,
+            co2: None,
+        };
+        for _ in 0..3 {
+            logger.log_reading(&reading, &reading, false).unwrap();
+        }
+        assert_eq!((logger.records, logger.dropped_records), (3, 1));
+        logger.finish().unwrap();
+
+        let last = fs::read_to_string(dir.join("sensor.2024-03-01-14.txt")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(last, "2024-03-01 14:00:00, 21.50, 45.25, 80.00\n");
+    }
+
+    #[test]
+    fn alert_log_records_each_alert() {
+        let path = std::env::temp_dir().join(format!("alert_log_{}.jsonl", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let _ = fs::remove_file(&path);
+        let mut logger = FileLogger {
+            alert_log: Some((path.clone(), BufWriter::new(File::create(&path).unwrap()))),
+            ..FileLogger::new("unused.txt")
+        };
+        let alert = Alert {
+            sensor: Channel::Humidity,
+            kind: AlertKind::Low,
+            value: 12.345,
+            message: "Humidity below minimum: 12.35%".to_string(),
+        };
+        logger.log_to_alert_log(&alert).unwrap();
+
+        let logged = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let line: serde_json::Value = serde_json::from_str(logged.trim_end()).unwrap();
+        assert!(parse_log_timestamp(line["timestamp"].as_str().unwrap()).is_some());
+        assert_eq!(line["sensor"], "humidity");
+        assert_eq!(line["category"], "low");
+        assert_eq!(line["value"].as_f64(), Some(12.35));
+        assert_eq!(line["message"], alert.message.as_str());
+    }
+
+    #[test]
+    fn failed_alert_log_writes_are_counted() {
+        let path = std::env::temp_dir().join(format!("alert_log_ro_{}.jsonl", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        // A file opened only for reading can't be written, even by root
+        File::create(&path).unwrap();
+        let mut logger = FileLogger {
+            alert_log: Some((path.clone(), BufWriter::new(File::open(&path).unwrap()))),
+            ..FileLogger::new("unused.txt")
+        };
+        let alert = Alert {
+            sensor: Channel::Temperature,
+            kind: AlertKind::High,
+            value: 31.2,
+            message: "Temperature exceeded threshold: 31.20°C".to_string(),
+        };
+        logger.log_to_alert_log(&alert).unwrap();
+        logger.log_to_alert_log(&alert).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((logger.records, logger.dropped_records), (2, 2));
+    }
+
+    // /dev/full accepts opening but fails every write as if the disk were full
+    #[cfg(all(feature = "parquet", target_os = "linux"))]
+    #[test]
+    fn failed_parquet_writes_are_counted_and_logging_goes_on() {
+        let mut logger = FileLogger {
+            parquet: Some(
+                ParquetWriter::create("/dev/full", NanRepresentation::default(), false).unwrap(),
+            ),
+            ..FileLogger::new("/dev/full")
+        };
+        let reading = Reading {
+            temperature: 21.5,
+            humidity: 45.25,
+            light: 80.0,
+            pressure: None,
+            co2: None,
+        };
+        // Rows are buffered until enough of them reach the file to fail
+        while logger.dropped_records == 0 {
+            logger.log_reading(&reading, &reading, false).unwrap();
+        }
+        assert!(logger.failures.disk_full);
+        let dropped = logger.dropped_records;
+        logger.log_reading(&reading, &reading, false).unwrap();
+        assert_eq!(logger.dropped_records, dropped + 1);
+        logger.finish().unwrap();
+    }
+}
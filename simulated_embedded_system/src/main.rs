@@ -1,355 +1,829 @@
-use rand::Rng;
-use std::fs::OpenOptions;
-use std::io::{self, Read, Write, Result};
-use chrono::Local;
-use std::thread;
-use std::time::{Duration, Instant};
-use serde::Deserialize;
-use std::fs;
+use chrono::NaiveDateTime;
+use clap::{Parser, Subcommand, ValueEnum};
+use simulated_embedded_system::alerts::{Alert, AlertKind};
+use simulated_embedded_system::analysis::{analyze_log, export_log_summary, suggest_thresholds};
+use simulated_embedded_system::bus::Channel;
+use simulated_embedded_system::config::{
+    config_error_exit_code, default_read_timeout_ms, Config, MenuStyle, OnComplete, OutOfRange,
+    RunConfig, SensorSource, StdinEof, SystemMetric,
+};
+use simulated_embedded_system::display::{
+    clear_chained_input, clear_screen, read_input, set_chained_input, set_headless, ConsoleDisplay,
+    Display,
+};
+use simulated_embedded_system::history::{RingBuffer, SharedHistory};
+use simulated_embedded_system::metrics::{self, Metrics, SharedMetrics};
+use simulated_embedded_system::notify::Notifier;
+use simulated_embedded_system::replay::{parse_timestamp, replay_log, ReplayOptions};
+use simulated_embedded_system::scenario::{list_scenarios, Scenario};
+use simulated_embedded_system::sensor::check_source;
+use simulated_embedded_system::server;
+use simulated_embedded_system::simulation::{run_simulation, RunOptions, RunOutcome};
+use std::collections::BTreeMap;
 use std::error::Error;
-
-// Configuration structure
-#[derive(Debug, Deserialize)]
-struct Config {
-    sensors: SensorConfig,
-    storage: StorageConfig,
-    display: DisplayConfig,
-    alerts: AlertConfig,
-}
-
-#[derive(Debug, Deserialize)]
-struct SensorConfig {
-    temperature_sampling_rate: u64,
-    humidity_sampling_rate: u64,
-    light_sampling_rate: u64,
+use std::fs;
+use std::io::{self, Write};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+
+// Command-line interface
+#[derive(Parser)]
+#[command(version, about = "Simulated embedded system with virtual sensors")]
+struct Cli {
+    /// Path to the configuration file
+    #[arg(long, global = true, default_value = "config.toml")]
+    config: String,
+
+    /// Overwrite an existing log file in truncate mode without asking
+    #[arg(long, global = true)]
+    force: bool,
+
+    /// Tag the run with metadata, e.g. `--tag operator=alice` (repeatable)
+    #[arg(long = "tag", global = true, value_name = "KEY=VALUE", value_parser = parse_tag)]
+    tags: Vec<(String, String)>,
+
+    /// Run a simulation without the interactive menu, like the `run` subcommand
+    #[arg(long)]
+    run: bool,
+
+    /// Simulation duration in seconds for `--run` or `run` [default: 30]
+    #[arg(long, global = true)]
+    duration: Option<u64>,
+
+    /// Don't clear the screen, so piped output stays clean
+    #[arg(long, global = true)]
+    headless: bool,
+
+    /// Actions after each run, in order: menu, repeat, export or exit
+    /// (overrides `on_complete` under `[run]`)
+    #[arg(long, global = true, value_delimiter = ',', value_name = "ACTIONS")]
+    on_complete: Vec<OnComplete>,
+
+    /// Check a configuration file, print a summary of it and exit without
+    /// running anything
+    #[arg(long, value_name = "PATH", conflicts_with = "run")]
+    validate_config: Option<String>,
+
+    /// Without a subcommand or `--run` the interactive menu is started
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Debug, Deserialize)]
-struct StorageConfig {
-    log_file_path: String,
+#[derive(Subcommand)]
+enum Command {
+    /// Run a simulation without the interactive menu
+    Run {
+        /// Play back a built-in scenario by name, or a scenario file
+        #[arg(long, conflicts_with = "replay")]
+        scenario: Option<String>,
+        /// Return the readings of a recorded text or CSV log instead of
+        /// simulated values, ending the run when they run out
+        #[arg(long, value_name = "PATH")]
+        replay: Option<String>,
+    },
+    /// List the built-in scenarios
+    Scenarios,
+    /// Replay a recorded log through the display and alerts
+    Replay {
+        /// Log file to replay (defaults to the configured log file)
+        log: Option<String>,
+        /// Skip rows before this time, e.g. "2024-05-01 15:00:00"
+        #[arg(long, value_parser = parse_timestamp)]
+        from: Option<NaiveDateTime>,
+        /// Skip rows after this time, e.g. "2024-05-01 15:10:00"
+        #[arg(long, value_parser = parse_timestamp)]
+        to: Option<NaiveDateTime>,
+        /// Playback speed relative to the recorded timing
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+    },
+    /// Summarize a recorded log: statistics, histograms and threshold exceedances
+    Analyze {
+        /// Log file to analyse (defaults to the configured log file)
+        log: Option<String>,
+        /// Print the summary as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Suggest alert thresholds from the distribution of logged readings
+    SuggestThresholds {
+        /// Log file to analyse (defaults to the configured log file)
+        log: Option<String>,
+    },
+    /// Send a sample alert to every configured alert sink and report each delivery
+    TestAlert {
+        /// Severity named in the sample alert
+        #[arg(long, value_enum, default_value_t = Severity::Warning)]
+        severity: Severity,
+    },
 }
 
-#[derive(Debug, Deserialize)]
-struct DisplayConfig {
-    real_time_graph: bool,
+// Severity of a test alert; it only appears in the alert's message
+#[derive(Clone, Copy, ValueEnum)]
+enum Severity {
+    Info,
+    Warning,
+    Critical,
 }
 
-#[derive(Debug, Deserialize)]
-struct AlertConfig {
-    temperature_threshold: f32,
-    humidity_threshold: f32,
-    light_threshold: f32,
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
 }
 
-impl Config {
-    fn load(path: &str) -> std::result::Result<Self, Box<dyn Error>> {
-        let config_str = fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&config_str)?;
-        Ok(config)
+// Parse a `--tag key=value` argument. Commas would split the log's run line.
+fn parse_tag(tag: &str) -> std::result::Result<(String, String), String> {
+    let (key, value) = tag
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{}`", tag))?;
+    let (key, value) = (key.trim(), value.trim());
+    if key.is_empty() {
+        return Err(format!("tag `{}` has an empty key", tag));
+    }
+    if tag.contains([',', '\n']) {
+        return Err(format!(
+            "tag `{}` must not contain commas or line breaks",
+            tag
+        ));
     }
+    Ok((key.to_string(), value.to_string()))
 }
 
-// Simulated Virtual Sensor
-struct VirtualSensor;
+// Duration of a menu run when none is chosen
+const DEFAULT_DURATION_SECS: u64 = 30;
 
-impl VirtualSensor {
-    fn new() -> Self {
-        VirtualSensor
-    }
+// Ask the user for the simulation duration, until one within the
+// configured range is chosen. Once stdin is closed the default duration is
+// used with `on_stdin_eof = "run"`, otherwise `None` cancels the run.
+fn prompt_duration(config: &Config) -> std::result::Result<Option<u64>, Box<dyn Error>> {
+    let limits = &config.run;
+    loop {
+        println!("Choose simulation duration:");
+        println!("1. 15 seconds");
+        println!("2. 30 seconds");
+        println!("3. Other");
+        print!("> ");
+        io::stdout().flush()?;
+
+        let Some(choice) = read_input()? else {
+            return Ok(default_on_eof(config));
+        };
 
-    fn read_temperature(&self) -> f32 {
-        let mut rng = rand::thread_rng();
-        rng.gen_range(20.0..30.0) // Simulate temperature between 20°C and 30°C
+        let duration_seconds = match choice.as_str() {
+            "1" => 15,
+            "2" => 30,
+            "3" => {
+                print!("Duration in seconds: ");
+                io::stdout().flush()?;
+                let Some(input) = read_input()? else {
+                    return Ok(default_on_eof(config));
+                };
+                match input.parse() {
+                    Ok(seconds) => seconds,
+                    Err(_) => {
+                        println!("Invalid duration!");
+                        continue;
+                    }
+                }
+            }
+            _ => {
+                println!(
+                    "Invalid choice! Defaulting to {} seconds.",
+                    DEFAULT_DURATION_SECS
+                );
+                DEFAULT_DURATION_SECS
+            }
+        };
+        match limits.check_duration(duration_seconds) {
+            Ok(()) => return Ok(Some(duration_seconds)),
+            Err(e) => println!("[WARNING]: Rejected duration: {}", e),
+        }
     }
+}
 
-    fn read_humidity(&self) -> f32 {
-        let mut rng = rand::thread_rng();
-        rng.gen_range(30.0..70.0) // Simulate humidity between 30% and 70%
+// Duration to run for once stdin is closed, if `on_stdin_eof` runs at all
+fn default_on_eof(config: &Config) -> Option<u64> {
+    match config.on_stdin_eof {
+        StdinEof::Exit => None,
+        StdinEof::Run => Some(config.run.clamp_duration(DEFAULT_DURATION_SECS)),
     }
+}
 
-    fn read_light_intensity(&self) -> f32 {
-        let mut rng = rand::thread_rng();
-        rng.gen_range(0.0..100.0) // Simulate light intensity between 0% and 100%
+// Apply the configured duration range to `run --duration`, clamping or
+// failing as set by `out_of_range`
+fn limit_duration(limits: &RunConfig, seconds: u64) -> std::result::Result<u64, Box<dyn Error>> {
+    let Err(e) = limits.check_duration(seconds) else {
+        return Ok(seconds);
+    };
+    match limits.out_of_range {
+        OutOfRange::Error => Err(e.into()),
+        OutOfRange::Clamp => {
+            let clamped = limits.clamp_duration(seconds);
+            println!("[Config]: Clamped duration to {} seconds: {}", clamped, e);
+            Ok(clamped)
+        }
     }
 }
 
-// Console Display
-struct ConsoleDisplay;
+// Adjust settings, returning whether any were changed
+fn adjust_settings(config: &mut Config) -> std::result::Result<bool, Box<dyn Error>> {
+    let mut changed = false;
+    loop {
+        clear_menu(config.menu_style);
+        println!("=== Current Settings ===");
+        for (number, name, channel) in [
+            (1, "Temperature", Channel::Temperature),
+            (2, "Humidity", Channel::Humidity),
+            (3, "Light", Channel::Light),
+        ] {
+            let rate = config.sensors.sampling_rate(channel).unwrap_or_default();
+            println!("{}. {} Sampling Rate: {} ms", number, name, rate.as_millis());
+        }
+        println!(
+            "4. Temperature Alert Threshold: {}{}",
+            config.alerts.temperature_threshold,
+            config.display.temperature_unit.symbol()
+        );
+        println!("5. Humidity Alert Threshold: {}%", config.alerts.humidity_threshold);
+        println!("6. Light Alert Threshold: {}%", config.alerts.light_threshold);
+        println!("7. Save Settings");
+        println!("8. Back to Main Menu");
+        print!("> ");
+        io::stdout().flush()?;
 
-impl ConsoleDisplay {
-    fn new() -> Self {
-        ConsoleDisplay
-    }
+        let Some(choice) = read_input()? else {
+            break;
+        };
 
-    fn show(&self, data: &str) {
-        println!("[LCD Display]: {}", data);
+        changed |= match choice.as_str() {
+            "1" => {
+                println!("Enter new Temperature Sampling Rate (in milliseconds):");
+                let Some(input) = read_input()? else {
+                    break;
+                };
+                config
+                    .sensors
+                    .set_sampling_rate_ms(Channel::Temperature, input.parse()?);
+                true
+            }
+            "2" => {
+                println!("Enter new Humidity Sampling Rate (in milliseconds):");
+                let Some(input) = read_input()? else {
+                    break;
+                };
+                config
+                    .sensors
+                    .set_sampling_rate_ms(Channel::Humidity, input.parse()?);
+                true
+            }
+            "3" => {
+                println!("Enter new Light Sampling Rate (in milliseconds):");
+                let Some(input) = read_input()? else {
+                    break;
+                };
+                config
+                    .sensors
+                    .set_sampling_rate_ms(Channel::Light, input.parse()?);
+                true
+            }
+            "4" => {
+                println!(
+                    "Enter new Temperature Alert Threshold (in {}):",
+                    config.display.temperature_unit.symbol()
+                );
+                let Some(input) = read_input()? else {
+                    break;
+                };
+                config.alerts.temperature_threshold = input.parse()?;
+                true
+            }
+            "5" => {
+                println!("Enter new Humidity Alert Threshold (in %):");
+                let Some(input) = read_input()? else {
+                    break;
+                };
+                config.alerts.humidity_threshold = input.parse()?;
+                true
+            }
+            "6" => {
+                println!("Enter new Light Alert Threshold (in %):");
+                let Some(input) = read_input()? else {
+                    break;
+                };
+                config.alerts.light_threshold = input.parse()?;
+                true
+            }
+            "7" => {
+                changed &= !save_settings(config);
+                pause(config.menu_style)?;
+                false
+            }
+            "8" => break,
+            _ => {
+                println!("Invalid choice!");
+                false
+            }
+        };
     }
+    Ok(changed)
+}
+
+// Select where each sensor's readings come from, returning whether any changed
+fn select_sensor_sources(config: &mut Config) -> std::result::Result<bool, Box<dyn Error>> {
+    let mut changed = false;
+    loop {
+        clear_menu(config.menu_style);
+        println!("=== Sensor Sources ===");
+        println!("1. Temperature: {}", config.sensors.temperature_source);
+        println!("2. Humidity: {}", config.sensors.humidity_source);
+        println!("3. Light: {}", config.sensors.light_source);
+        println!("4. Back to Main Menu");
+        print!("> ");
+        io::stdout().flush()?;
+
+        let Some(choice) = read_input()? else {
+            break;
+        };
 
-    fn show_graph(&self, values: &[f32]) {
-        println!("[Real-Time Graph]");
-        for &value in values {
-            let bar = "=".repeat(value as usize / 2); // Scale for better visualization
-            println!("{:5.2} | {}", value, bar);
+        let (name, source) = match choice.as_str() {
+            "1" => ("Temperature", &mut config.sensors.temperature_source),
+            "2" => ("Humidity", &mut config.sensors.humidity_source),
+            "3" => ("Light", &mut config.sensors.light_source),
+            "4" => break,
+            _ => {
+                println!("Invalid choice!");
+                continue;
+            }
+        };
+
+        // Keep the read timeout of a host metric that is already selected
+        let read_timeout_ms = match *source {
+            SensorSource::System {
+                read_timeout_ms, ..
+            } => read_timeout_ms,
+            SensorSource::Virtual => default_read_timeout_ms(),
+        };
+        let sources: Vec<SensorSource> = std::iter::once(SensorSource::Virtual)
+            .chain(SystemMetric::ALL.map(|metric| SensorSource::System {
+                metric,
+                read_timeout_ms,
+            }))
+            .collect();
+        println!("Select the {} source:", name);
+        for (i, source) in sources.iter().enumerate() {
+            println!("{}. {}", i + 1, source);
         }
-    }
+        print!("> ");
+        io::stdout().flush()?;
 
-    fn show_alert(&self, message: &str) {
-        println!("[ALERT]: {}", message);
+        let Some(input) = read_input()? else {
+            break;
+        };
+        let selected = input
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| sources.get(n.wrapping_sub(1)));
+        match selected {
+            Some(&selected) => match check_source(selected) {
+                Ok(()) => {
+                    changed |= *source != selected;
+                    *source = selected;
+                }
+                Err(e) => {
+                    println!("[WARNING]: {}; keeping {}", e, source);
+                    pause(config.menu_style)?;
+                }
+            },
+            None => println!("Invalid choice!"),
+        }
     }
+    Ok(changed)
 }
 
-// File Logger
-struct FileLogger {
-    file_path: String,
+// View logs
+fn view_logs(log_path: &str, style: MenuStyle) -> std::result::Result<(), Box<dyn Error>> {
+    clear_menu(style);
+    println!("=== Sensor Logs ===");
+    let logs = fs::read_to_string(log_path)?;
+    println!("{}", logs);
+    pause(style)
 }
 
-impl FileLogger {
-    fn new(file_path: &str) -> Self {
-        FileLogger {
-            file_path: file_path.to_string(),
-        }
+// Clear the screen before a menu, unless `menu_style = "compact"`
+fn clear_menu(style: MenuStyle) {
+    if style == MenuStyle::Full {
+        clear_screen();
+    }
+}
+
+// Wait for Enter before the next menu clears the screen
+fn pause(style: MenuStyle) -> std::result::Result<(), Box<dyn Error>> {
+    if style == MenuStyle::Full {
+        println!("Press Enter to continue...");
+        read_input()?;
     }
+    Ok(())
+}
 
-    fn log(&self, data: &str) -> Result<()> {
-        let mut file = OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(&self.file_path)?;
+// Interactive menu, returning whether settings were changed
+fn interactive_menu(
+    config: &mut Config,
+    history: Option<&SharedHistory>,
+    metrics: Option<&SharedMetrics>,
+    force: bool,
+    tags: &BTreeMap<String, String>,
+    on_complete: &[OnComplete],
+) -> std::result::Result<bool, Box<dyn Error>> {
+    let mut settings_changed = false;
+    set_chained_input(config.menu_style == MenuStyle::Compact);
+    loop {
+        match config.menu_style {
+            MenuStyle::Full => {
+                clear_screen();
+                println!("=== Embedded System Simulator ===");
+                println!("1. Start Simulation");
+                println!("2. Adjust Settings");
+                println!("3. View Logs");
+                println!("4. Select Sensor Source");
+                println!("5. Exit");
+                print!("> ");
+            }
+            MenuStyle::Compact => print!("[1]run [2]settings [3]logs [4]sources [5]quit > "),
+        }
+        io::stdout().flush()?;
 
-        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
-        writeln!(file, "{}, {}", timestamp, data)?;
+        // Once stdin is closed, leave the menu or do one run without it
+        let Some(choice) = read_input()? else {
+            println!("Input closed.");
+            if let Some(duration_seconds) = default_on_eof(config) {
+                let options = RunOptions {
+                    duration_seconds,
+                    interactive: false,
+                    force,
+                    scenario: None,
+                    tags: tags.clone(),
+                    replay_file: None,
+                };
+                run_with_actions(config, history, metrics, &options, on_complete)?;
+            }
+            break;
+        };
 
-        Ok(())
+        match choice.as_str() {
+            "1" => {
+                let Some(duration_seconds) = prompt_duration(config)? else {
+                    continue;
+                };
+                let options = RunOptions {
+                    duration_seconds,
+                    interactive: true,
+                    force,
+                    scenario: None,
+                    tags: tags.clone(),
+                    replay_file: None,
+                };
+                let (_, next) = run_with_actions(config, history, metrics, &options, on_complete)?;
+                if next == AfterRun::Exit {
+                    break;
+                }
+            }
+            "2" => settings_changed |= adjust_settings(config)?,
+            "3" => view_logs(&config.storage.log_file_path, config.menu_style)?,
+            "4" => settings_changed |= select_sensor_sources(config)?,
+            "5" => break,
+            _ => {
+                println!("Invalid choice!");
+                // The rest of a chain may have depended on this command
+                clear_chained_input();
+            }
+        }
     }
+
+    println!("Exiting...");
+    Ok(settings_changed)
 }
 
-// Statistics utility
-fn calculate_statistics(values: &[f32]) -> (f32, f32, f32) {
-    let sum: f32 = values.iter().sum();
-    let count = values.len() as f32;
-    let average = sum / count;
-    let min = values.iter().cloned().fold(f32::INFINITY, f32::min);
-    let max = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
-    (average, min, max)
+// Where the program goes once a run's actions are done
+#[derive(PartialEq)]
+enum AfterRun {
+    Menu,
+    Exit,
 }
 
-// Alert utility
-fn check_alerts(temperature: f32, humidity: f32, light: f32, config: &AlertConfig) -> Vec<String> {
-    let mut alerts = Vec::new();
-    if temperature > config.temperature_threshold {
-        alerts.push(format!("Temperature exceeded threshold: {:.2}°C", temperature));
-    }
-    if humidity > config.humidity_threshold {
-        alerts.push(format!("Humidity exceeded threshold: {:.2}%", humidity));
-    }
-    if light > config.light_threshold {
-        alerts.push(format!("Light intensity exceeded threshold: {:.2}%", light));
+// Run the simulation and then carry out the `on_complete` actions in order,
+// until one of them returns to the menu, exits or starts another run. Runs
+// are repeated with the same options, unless a run was stopped early. Returns
+// the highest exit code of the runs.
+fn run_with_actions(
+    config: &Config,
+    history: Option<&SharedHistory>,
+    metrics: Option<&SharedMetrics>,
+    options: &RunOptions,
+    on_complete: &[OnComplete],
+) -> std::result::Result<(u8, AfterRun), Box<dyn Error>> {
+    let mut exit_code = 0;
+    loop {
+        let outcome = run_simulation(config, history, metrics, options)?;
+        exit_code = exit_code.max(outcome.exit_code(&config.exit_codes));
+        match after_run(config, &outcome, on_complete)? {
+            Some(next) => return Ok((exit_code, next)),
+            None => println!("Starting another run..."),
+        }
     }
-    alerts
 }
 
-// Clear the screen
-fn clear_screen() {
-    print!("\x1B[2J\x1B[1;1H");
+// Carry out the actions after one run, returning `None` to run again
+fn after_run(
+    config: &Config,
+    outcome: &RunOutcome,
+    on_complete: &[OnComplete],
+) -> std::result::Result<Option<AfterRun>, Box<dyn Error>> {
+    for action in on_complete {
+        match action {
+            OnComplete::Menu => return Ok(Some(AfterRun::Menu)),
+            OnComplete::Exit => return Ok(Some(AfterRun::Exit)),
+            OnComplete::Export => {
+                let export_path = config.run.export_path();
+                export_log_summary(&config.storage.log_file_path, config, export_path)?;
+                println!("Exported the log summary to {}", export_path);
+            }
+            OnComplete::Repeat if outcome.stopped_early => {
+                println!("Not repeating: the run was stopped early.");
+                return Ok(Some(AfterRun::Menu));
+            }
+            OnComplete::Repeat => return Ok(None),
+        }
+    }
+    Ok(Some(AfterRun::Menu))
 }
 
-// Run the simulation
-fn run_simulation(config: &Config) -> std::result::Result<(), Box<dyn Error>> {
-    let sensor = VirtualSensor::new();
-    let display = ConsoleDisplay::new();
-    let logger = FileLogger::new(&config.storage.log_file_path);
-    let mut temperature_values: Vec<f32> = Vec::new();
-
-    // Ask the user for simulation duration
-    println!("Choose simulation duration:");
-    println!("1. 15 seconds");
-    println!("2. 30 seconds");
-    print!("> ");
-    io::stdout().flush()?;
-
-    let mut choice = String::new();
-    io::stdin().read_line(&mut choice)?;
-
-    let duration_seconds = match choice.trim() {
-        "1" => 15,
-        "2" => 30,
-        _ => {
-            println!("Invalid choice! Defaulting to 30 seconds.");
-            30
+// Main Function: load the configuration and exit with the run's outcome
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if let Some(path) = &cli.validate_config {
+        if cli.command.is_some() {
+            eprintln!("Error: --validate-config can't be combined with a subcommand");
+            return ExitCode::FAILURE;
         }
-    };
-
-    println!("Starting simulation for {} seconds...", duration_seconds);
-    let start_time = Instant::now();
-    let mut last_temperature_time = Instant::now();
-    let mut last_humidity_time = Instant::now();
-    let mut last_light_time = Instant::now();
-
-    loop {
-        // Check if the duration has elapsed
-        if start_time.elapsed() >= Duration::from_secs(duration_seconds) {
-            println!("Simulation completed after {} seconds.", duration_seconds);
-            break;
+        return validate_config(path);
+    }
+    let mut config = match Config::load(&cli.config) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: failed to load {}: {}", cli.config, e);
+            return ExitCode::from(config_error_exit_code());
         }
+    };
 
-        // Read sensor data based on sampling rates
-        let mut temperature = None;
-        let mut humidity = None;
-        let mut light = None;
-
-        if last_temperature_time.elapsed() >= Duration::from_secs(config.sensors.temperature_sampling_rate) {
-            temperature = Some(sensor.read_temperature());
-            last_temperature_time = Instant::now();
+    match run_command(cli, &mut config) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
         }
+    }
+}
 
-        if last_humidity_time.elapsed() >= Duration::from_secs(config.sensors.humidity_sampling_rate) {
-            humidity = Some(sensor.read_humidity());
-            last_humidity_time = Instant::now();
+// Load the config at `path` like a run would and report whether it is valid
+fn validate_config(path: &str) -> ExitCode {
+    let config = match Config::load(path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: failed to load {}: {}", path, e);
+            return ExitCode::FAILURE;
         }
+    };
+    println!("Config OK: {}", path);
+    let rates: Vec<_> = [
+        ("temperature", Channel::Temperature),
+        ("humidity", Channel::Humidity),
+        ("light", Channel::Light),
+        ("pressure", Channel::Pressure),
+        ("co2", Channel::Co2),
+    ]
+    .into_iter()
+    .filter_map(|(name, channel)| {
+        let rate = config.sensors.sampling_rate(channel)?;
+        Some(format!("{} {} ms", name, rate.as_millis()))
+    })
+    .collect();
+    println!(
+        "  Sampling rates: {} (tick {} ms)",
+        rates.join(", "),
+        config.sensors.tick_interval_ms
+    );
+    let alerts = &config.alerts;
+    let mut thresholds = vec![
+        format!(
+            "temperature {}{}",
+            alerts.temperature_threshold,
+            config.display.temperature_unit.symbol()
+        ),
+        format!("humidity {}%", alerts.humidity_threshold),
+        format!("light {}%", alerts.light_threshold),
+    ];
+    if let Some(threshold) = alerts.pressure_threshold {
+        thresholds.push(format!("pressure {} hPa", threshold));
+    }
+    if let Some(threshold) = alerts.co2_threshold {
+        thresholds.push(format!("co2 {} ppm", threshold));
+    }
+    println!("  Alert thresholds: {}", thresholds.join(", "));
+    let storage = &config.storage;
+    println!(
+        "  Log: {} ({}, {})",
+        storage.log_file_path,
+        format!("{:?}", storage.log_format).to_lowercase(),
+        format!("{:?}", storage.log_mode).to_lowercase()
+    );
+    ExitCode::SUCCESS
+}
 
-        if last_light_time.elapsed() >= Duration::from_secs(config.sensors.light_sampling_rate) {
-            light = Some(sensor.read_light_intensity());
-            last_light_time = Instant::now();
+// Dispatch to a subcommand or the interactive menu
+fn run_command(cli: Cli, config: &mut Config) -> std::result::Result<ExitCode, Box<dyn Error>> {
+    set_headless(cli.headless);
+    let command = match cli.command {
+        None if cli.run => Some(Command::Run {
+            scenario: None,
+            replay: None,
+        }),
+        Some(command) if cli.run && !matches!(command, Command::Run { .. }) => {
+            return Err("--run can't be combined with another subcommand".into());
         }
-
-        // Display sensor data
-        if let (Some(temp), Some(hum), Some(lgt)) = (temperature, humidity, light) {
-            display.show(&format!(
-                "Temperature: {:.2}°C, Humidity: {:.2}%, Light: {:.2}%",
-                temp, hum, lgt
-            ));
-
-            // Log sensor data to a file
-            logger
-                .log(&format!("{:.2}, {:.2}, {:.2}", temp, hum, lgt))
-                .expect("Failed to log data");
-
-            // Update and display real-time graph
-            temperature_values.push(temp);
-            if temperature_values.len() > 10 {
-                temperature_values.remove(0); // Keep only the last 10 values
-            }
-            if config.display.real_time_graph {
-                display.show_graph(&temperature_values);
+        command => command,
+    };
+    if cli.duration.is_some() && !matches!(command, Some(Command::Run { .. })) {
+        return Err("--duration needs `--run` or the `run` subcommand".into());
+    }
+    let on_complete = if cli.on_complete.is_empty() {
+        config.run.on_complete.clone()
+    } else {
+        cli.on_complete
+    };
+    match command {
+        Some(Command::Run { scenario, replay }) => {
+            let duration = cli.duration.unwrap_or(DEFAULT_DURATION_SECS);
+            let duration = limit_duration(&config.run, duration)?;
+            if scenario.is_some() && config.sensors.replay_file.is_some() {
+                return Err(
+                    "--scenario can't be combined with `replay_file` under [sensors]".into(),
+                );
             }
-
-            // Check for alerts
-            let alerts = check_alerts(temp, hum, lgt, &config.alerts);
-            for alert in alerts {
-                display.show_alert(&alert);
+            let scenario = scenario.as_deref().map(Scenario::load).transpose()?;
+            let history = start_server(config)?;
+            let metrics = start_metrics_server(config)?;
+            let options = RunOptions {
+                duration_seconds: duration,
+                interactive: false,
+                force: cli.force,
+                scenario,
+                tags: cli.tags.into_iter().collect(),
+                replay_file: replay,
+            };
+            // Without a menu to return to, "menu" ends the command too
+            let (exit_code, _) = run_with_actions(
+                config,
+                history.as_ref(),
+                metrics.as_ref(),
+                &options,
+                &on_complete,
+            )?;
+            Ok(ExitCode::from(exit_code))
+        }
+        Some(Command::Replay {
+            log,
+            from,
+            to,
+            speed,
+        }) => {
+            let log_path = log.as_deref().unwrap_or(&config.storage.log_file_path);
+            replay_log(log_path, config, &ReplayOptions { from, to, speed })?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Scenarios) => {
+            list_scenarios()?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Analyze { log, json }) => {
+            let log_path = log.as_deref().unwrap_or(&config.storage.log_file_path);
+            analyze_log(log_path, config, json)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::SuggestThresholds { log }) => {
+            let log_path = log.as_deref().unwrap_or(&config.storage.log_file_path);
+            suggest_thresholds(log_path, config)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::TestAlert { severity }) => test_alert(config, severity),
+        None => {
+            let history = start_server(config)?;
+            let metrics = start_metrics_server(config)?;
+            let tags = cli.tags.into_iter().collect();
+            let settings_changed = interactive_menu(
+                config,
+                history.as_ref(),
+                metrics.as_ref(),
+                cli.force,
+                &tags,
+                &on_complete,
+            )?;
+            if settings_changed && config.auto_save_settings {
+                save_settings(config);
             }
-
-            // Calculate and display statistics
-            let (avg, min, max) = calculate_statistics(&temperature_values);
-            println!("[Statistics] Average: {:.2}°C, Min: {:.2}°C, Max: {:.2}°C", avg, min, max);
+            Ok(ExitCode::SUCCESS)
         }
-
-        // Simulate a delay (e.g., 1 second)
-        thread::sleep(Duration::from_secs(1));
     }
-    Ok(())
 }
 
-// Adjust settings
-fn adjust_settings(config: &mut Config) -> std::result::Result<(), Box<dyn Error>> {
-    loop {
-        clear_screen();
-        println!("=== Current Settings ===");
-        println!("1. Temperature Sampling Rate: {}s", config.sensors.temperature_sampling_rate);
-        println!("2. Humidity Sampling Rate: {}s", config.sensors.humidity_sampling_rate);
-        println!("3. Light Sampling Rate: {}s", config.sensors.light_sampling_rate);
-        println!("4. Temperature Alert Threshold: {}°C", config.alerts.temperature_threshold);
-        println!("5. Humidity Alert Threshold: {}%", config.alerts.humidity_threshold);
-        println!("6. Light Alert Threshold: {}%", config.alerts.light_threshold);
-        println!("7. Back to Main Menu");
-        print!("> ");
-        io::stdout().flush()?;
-
-        let mut choice = String::new();
-        io::stdin().read_line(&mut choice)?;
+// Push a sample alert through the console and every notification sink, as a
+// smoke test of the alert integrations. It is not written to the log, which
+// would count it as a real alert on replay.
+fn test_alert(
+    config: &Config,
+    severity: Severity,
+) -> std::result::Result<ExitCode, Box<dyn Error>> {
+    let alert = Alert {
+        sensor: Channel::Temperature,
+        kind: AlertKind::High,
+        value: config.alerts.temperature_threshold,
+        message: format!(
+            "Test alert ({}): this is a test of the alert sinks",
+            severity.name()
+        ),
+    };
+    ConsoleDisplay::new().show_alert(&alert.message);
 
-        match choice.trim() {
-            "1" => {
-                println!("Enter new Temperature Sampling Rate (in seconds):");
-                let mut input = String::new();
-                io::stdin().read_line(&mut input)?;
-                config.sensors.temperature_sampling_rate = input.trim().parse()?;
-            }
-            "2" => {
-                println!("Enter new Humidity Sampling Rate (in seconds):");
-                let mut input = String::new();
-                io::stdin().read_line(&mut input)?;
-                config.sensors.humidity_sampling_rate = input.trim().parse()?;
-            }
-            "3" => {
-                println!("Enter new Light Sampling Rate (in seconds):");
-                let mut input = String::new();
-                io::stdin().read_line(&mut input)?;
-                config.sensors.light_sampling_rate = input.trim().parse()?;
-            }
-            "4" => {
-                println!("Enter new Temperature Alert Threshold (in °C):");
-                let mut input = String::new();
-                io::stdin().read_line(&mut input)?;
-                config.alerts.temperature_threshold = input.trim().parse()?;
-            }
-            "5" => {
-                println!("Enter new Humidity Alert Threshold (in %):");
-                let mut input = String::new();
-                io::stdin().read_line(&mut input)?;
-                config.alerts.humidity_threshold = input.trim().parse()?;
-            }
-            "6" => {
-                println!("Enter new Light Alert Threshold (in %):");
-                let mut input = String::new();
-                io::stdin().read_line(&mut input)?;
-                config.alerts.light_threshold = input.trim().parse()?;
+    let mut notifier = Notifier::new(&config.notifications, "test-alert");
+    let deliveries = notifier.send_now(&alert.message);
+    if deliveries.is_empty() {
+        println!("No alert sinks are configured under [notifications].");
+    }
+    let mut failed = false;
+    for (sink, result) in deliveries {
+        match result {
+            Ok(()) => println!("{}: delivered", sink),
+            Err(e) => {
+                println!("{}: failed: {}", sink, e);
+                failed = true;
             }
-            "7" => break,
-            _ => println!("Invalid choice!"),
         }
     }
-    Ok(())
+    Ok(if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
 }
 
-// View logs
-fn view_logs(log_path: &str) -> std::result::Result<(), Box<dyn Error>> {
-    clear_screen();
-    println!("=== Sensor Logs ===");
-    let logs = fs::read_to_string(log_path)?;
-    println!("{}", logs);
-    println!("Press Enter to continue...");
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
-    Ok(())
+// Write the adjusted settings back to the config file they were loaded from,
+// returning whether they were saved. A fetched config has no file to write to.
+fn save_settings(config: &Config) -> bool {
+    if config.is_remote() {
+        println!(
+            "[Config]: Not saving settings: {} was fetched remotely",
+            config.source
+        );
+        return false;
+    }
+    match config.save(&config.source) {
+        Ok(()) => {
+            println!("[Config]: Settings saved to {}", config.source);
+            true
+        }
+        Err(e) => {
+            println!("[WARNING]: Could not save settings to {}: {}", config.source, e);
+            false
+        }
+    }
 }
 
-// Main Function with Menu
-fn main() -> std::result::Result<(), Box<dyn Error>> {
-    let mut config = Config::load("config.toml")?;
-
-    loop {
-        clear_screen();
-        println!("=== Embedded System Simulator ===");
-        println!("1. Start Simulation");
-        println!("2. Adjust Settings");
-        println!("3. View Logs");
-        println!("4. Exit");
-        print!("> ");
-        io::stdout().flush()?;
-
-        let mut choice = String::new();
-        io::stdin().read_line(&mut choice)?;
-
-        match choice.trim() {
-            "1" => run_simulation(&config)?,
-            "2" => adjust_settings(&mut config)?,
-            "3" => view_logs(&config.storage.log_file_path)?,
-            "4" => break,
-            _ => println!("Invalid choice!"),
+// Start the HTTP server when configured
+fn start_server(config: &Config) -> std::result::Result<Option<SharedHistory>, Box<dyn Error>> {
+    match &config.server {
+        Some(server_config) => {
+            let history = Arc::new(Mutex::new(RingBuffer::new(server_config.buffer_size)));
+            server::spawn(server_config.port, Arc::clone(&history))?;
+            Ok(Some(history))
         }
+        None => Ok(None),
     }
+}
 
-    println!("Exiting...");
-    Ok(())
-}
\ No newline at end of file
+// Start the Prometheus metrics server when `metrics_port` is set
+fn start_metrics_server(
+    config: &Config,
+) -> std::result::Result<Option<SharedMetrics>, Box<dyn Error>> {
+    match config.display.metrics_port {
+        Some(port) => {
+            let metrics = Arc::new(Mutex::new(Metrics::new(config.display.temperature_unit)));
+            metrics::spawn(port, Arc::clone(&metrics))?;
+            Ok(Some(metrics))
+        }
+        None => Ok(None),
+    }
+}
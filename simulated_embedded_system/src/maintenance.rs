@@ -0,0 +1,30 @@
+use crate::signals::{SignalFlag, SIGUSR1};
+use std::io;
+
+// Operator-controlled maintenance mode, toggled by sending SIGUSR1 to the process.
+// While active, alerts are suppressed and counted instead of shown.
+pub struct MaintenanceMode {
+    pub active: bool,
+    pub suppressed_alerts: u64,
+    toggle: SignalFlag,
+}
+
+impl MaintenanceMode {
+    pub fn new() -> io::Result<Self> {
+        Ok(MaintenanceMode {
+            active: false,
+            suppressed_alerts: 0,
+            toggle: SignalFlag::register(SIGUSR1)?,
+        })
+    }
+
+    // Apply a pending toggle, returning the new state if it changed
+    pub fn poll(&mut self) -> Option<bool> {
+        if self.toggle.take() {
+            self.active = !self.active;
+            Some(self.active)
+        } else {
+            None
+        }
+    }
+}
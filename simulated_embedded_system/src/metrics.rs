@@ -0,0 +1,156 @@
+use crate::config::TemperatureUnit;
+use crate::sensor::Reading;
+use std::error::Error;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tiny_http::{Header, Response, Server};
+
+// Latest readings and the alert count, served in the Prometheus text format.
+// The counter keeps growing across runs, as Prometheus expects of a counter.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    reading: Option<Reading>,
+    alerts_total: u64,
+    temperature_unit: TemperatureUnit,
+}
+
+// Metrics shared between the simulation loop and the metrics server
+pub type SharedMetrics = Arc<Mutex<Metrics>>;
+
+impl Metrics {
+    pub fn new(temperature_unit: TemperatureUnit) -> Self {
+        Metrics {
+            temperature_unit,
+            ..Metrics::default()
+        }
+    }
+
+    pub fn record_reading(&mut self, reading: &Reading) {
+        self.reading = Some(*reading);
+    }
+
+    pub fn count_alerts(&mut self, count: u64) {
+        self.alerts_total += count;
+    }
+
+    // The metrics as a Prometheus text exposition. Gauges appear once the
+    // first reading is in; the optional sensors only when configured.
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        if let Some(reading) = &self.reading {
+            let temperature = match self.temperature_unit {
+                TemperatureUnit::Celsius => "sensor_temperature_celsius",
+                TemperatureUnit::Fahrenheit => "sensor_temperature_fahrenheit",
+            };
+            for (name, help, value) in [
+                (
+                    temperature,
+                    "Latest temperature reading",
+                    Some(reading.temperature),
+                ),
+                (
+                    "sensor_humidity_percent",
+                    "Latest humidity reading",
+                    Some(reading.humidity),
+                ),
+                (
+                    "sensor_light_percent",
+                    "Latest light reading",
+                    Some(reading.light),
+                ),
+                (
+                    "sensor_pressure_hpa",
+                    "Latest pressure reading",
+                    reading.pressure,
+                ),
+                ("sensor_co2_ppm", "Latest CO2 reading", reading.co2),
+            ] {
+                if let Some(value) = value {
+                    write_metric(&mut text, name, help, "gauge", &format_value(value));
+                }
+            }
+        }
+        write_metric(
+            &mut text,
+            "sensor_alerts_total",
+            "Alerts raised since the simulator started",
+            "counter",
+            &self.alerts_total.to_string(),
+        );
+        text
+    }
+}
+
+fn write_metric(text: &mut String, name: &str, help: &str, kind: &str, value: &str) {
+    let _ = writeln!(text, "# HELP {} {}", name, help);
+    let _ = writeln!(text, "# TYPE {} {}", name, kind);
+    let _ = writeln!(text, "{} {}", name, value);
+}
+
+// Failed readings are NaN, which Prometheus accepts as is
+fn format_value(value: f32) -> String {
+    match value {
+        f32::INFINITY => "+Inf".to_string(),
+        f32::NEG_INFINITY => "-Inf".to_string(),
+        value => value.to_string(),
+    }
+}
+
+// Start serving `/metrics` on a background thread
+pub fn spawn(port: u16, metrics: SharedMetrics) -> Result<(), Box<dyn Error>> {
+    let server = Server::http(("0.0.0.0", port)).map_err(|e| e.to_string())?;
+    println!("[Metrics]: Serving Prometheus metrics on port {}", port);
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let path = request.url().split('?').next().unwrap_or_default();
+            let response = if path == "/metrics" {
+                let body = metrics.lock().unwrap_or_else(|e| e.into_inner()).render();
+                let header =
+                    Header::from_bytes(&b"Content-Type"[..], &b"text/plain; version=0.0.4"[..])
+                        .expect("static header is valid");
+                Response::from_string(body).with_header(header)
+            } else {
+                Response::from_string("not found").with_status_code(404)
+            };
+            // A client hanging up early is not our problem
+            let _ = request.respond(response);
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_render_in_the_prometheus_text_format() {
+        let mut metrics = Metrics::default();
+        assert_eq!(
+            metrics.render(),
+            "# HELP sensor_alerts_total Alerts raised since the simulator started\n\
+             # TYPE sensor_alerts_total counter\n\
+             sensor_alerts_total 0\n"
+        );
+
+        metrics.record_reading(&Reading {
+            temperature: 21.5,
+            humidity: f32::NAN,
+            light: 40.0,
+            pressure: None,
+            co2: Some(650.0),
+        });
+        metrics.count_alerts(2);
+        let text = metrics.render();
+        assert!(text.contains(
+            "# TYPE sensor_temperature_celsius gauge\nsensor_temperature_celsius 21.5\n"
+        ));
+        assert!(text.contains("\nsensor_humidity_percent NaN\n"));
+        assert!(text.contains("\nsensor_co2_ppm 650\n"));
+        assert!(!text.contains("sensor_pressure_hpa"));
+        assert!(text.ends_with("sensor_alerts_total 2\n"));
+    }
+}
@@ -0,0 +1,137 @@
+use crate::bus::Channel;
+use crate::config::{MqttConfig, TemperatureUnit};
+use crate::logger::TIMESTAMP_FORMAT_MS;
+use crate::sensor::Reading;
+use chrono::Local;
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS, RecvTimeoutError};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// Publishes that can wait for the connection before new ones are dropped
+const QUEUE_CAPACITY: usize = 64;
+
+// How long the connection thread waits for network activity between checks
+// whether the run is over
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Pause before reconnecting to a broker that can't be reached
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    run_id: &'a str,
+    timestamp: String,
+    value: f32,
+    unit: &'static str,
+}
+
+// Publishes each reading to `<topic_prefix>/<sensor>` on an MQTT broker, like
+// an IoT device would. The connection is kept by a background thread that
+// reconnects as needed; publishing never blocks the run, and readings taken
+// while the broker can't be reached are dropped once the queue is full.
+pub struct MqttPublisher {
+    client: Client,
+    topic_prefix: String,
+    run_id: String,
+    temperature_unit: TemperatureUnit,
+    stop: Arc<AtomicBool>,
+}
+
+impl MqttPublisher {
+    pub fn connect(config: &MqttConfig, run_id: &str, temperature_unit: TemperatureUnit) -> Self {
+        let mut options = MqttOptions::new(run_id, config.broker.as_str(), config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        let (client, mut connection) = Client::new(options, QUEUE_CAPACITY);
+        let stop = Arc::new(AtomicBool::new(false));
+        let broker = format!("{}:{}", config.broker, config.port);
+        let stopped = Arc::clone(&stop);
+
+        thread::spawn(move || {
+            // Warn once per outage rather than on every reconnect attempt
+            let mut reachable = true;
+            loop {
+                match connection.recv_timeout(POLL_INTERVAL) {
+                    Ok(Ok(Event::Incoming(Packet::ConnAck(_)))) => {
+                        println!("[MQTT]: Connected to {}", broker);
+                        reachable = true;
+                    }
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => {
+                        if reachable {
+                            println!(
+                                "[WARNING]: MQTT broker {} unreachable, readings are not published: {}",
+                                broker, e
+                            );
+                            reachable = false;
+                        }
+                        if stopped.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        thread::sleep(RECONNECT_DELAY);
+                    }
+                    // Idle: the queued readings have all been sent
+                    Err(RecvTimeoutError::Timeout) if stopped.load(Ordering::Relaxed) => break,
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+
+        MqttPublisher {
+            client,
+            topic_prefix: config.topic_prefix.clone(),
+            run_id: run_id.to_string(),
+            temperature_unit,
+            stop,
+        }
+    }
+
+    // Publish the sensors read on this tick as JSON, e.g. `{"run_id": ...,
+    // "timestamp": ..., "value": 21.8, "unit": "°C"}`. Failed (NaN) readings
+    // are left out.
+    pub fn publish_reading(&self, reading: &Reading, read: &[Channel]) {
+        let timestamp = Local::now().format(TIMESTAMP_FORMAT_MS).to_string();
+        for (channel, name, unit, value) in [
+            (
+                Channel::Temperature,
+                "temperature",
+                self.temperature_unit.symbol(),
+                Some(reading.temperature),
+            ),
+            (Channel::Humidity, "humidity", "%", Some(reading.humidity)),
+            (Channel::Light, "light", "%", Some(reading.light)),
+            (Channel::Pressure, "pressure", "hPa", reading.pressure),
+            (Channel::Co2, "co2", "ppm", reading.co2),
+        ] {
+            let Some(value) = value.filter(|value| value.is_finite()) else {
+                continue;
+            };
+            if !read.contains(&channel) {
+                continue;
+            }
+            let payload = Payload {
+                run_id: &self.run_id,
+                timestamp: timestamp.clone(),
+                value,
+                unit,
+            };
+            let payload = serde_json::to_vec(&payload).expect("MQTT payload serializes");
+            let topic = format!("{}/{}", self.topic_prefix, name);
+            // A full queue means the broker is unreachable; the reading is dropped
+            let _ = self
+                .client
+                .try_publish(topic, QoS::AtMostOnce, false, payload);
+        }
+    }
+}
+
+impl Drop for MqttPublisher {
+    // Let the connection thread send what is queued and end
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.client.try_disconnect();
+    }
+}
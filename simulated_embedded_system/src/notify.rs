@@ -0,0 +1,183 @@
+use crate::config::{AlertChannel, NotificationConfig};
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+// Destination for alert notifications outside the console and log
+pub trait AlertSink {
+    fn name(&self) -> &str;
+    fn channel(&self) -> AlertChannel;
+    fn send(&mut self, run_id: &str, alerts: &[String]) -> Result<(), Box<dyn Error>>;
+}
+
+// Posts alerts as JSON to an HTTP endpoint
+#[cfg(feature = "webhook")]
+pub struct WebhookSink {
+    url: String,
+}
+
+#[cfg(feature = "webhook")]
+impl AlertSink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn channel(&self) -> AlertChannel {
+        AlertChannel::Webhook
+    }
+
+    fn send(&mut self, run_id: &str, alerts: &[String]) -> Result<(), Box<dyn Error>> {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(5))
+            .build();
+        agent.post(&self.url).send_json(serde_json::json!({
+            "run_id": run_id,
+            "alerts": alerts,
+        }))?;
+        Ok(())
+    }
+}
+
+// Sends alerts to the local syslog daemon as warnings of the user facility
+#[cfg(unix)]
+pub struct SyslogSink {
+    socket: std::os::unix::net::UnixDatagram,
+}
+
+#[cfg(unix)]
+impl SyslogSink {
+    pub fn new() -> std::io::Result<Self> {
+        let socket = std::os::unix::net::UnixDatagram::unbound()?;
+        socket.connect("/dev/log")?;
+        Ok(SyslogSink { socket })
+    }
+}
+
+#[cfg(unix)]
+impl AlertSink for SyslogSink {
+    fn name(&self) -> &str {
+        "syslog"
+    }
+
+    fn channel(&self) -> AlertChannel {
+        AlertChannel::Syslog
+    }
+
+    fn send(&mut self, run_id: &str, alerts: &[String]) -> Result<(), Box<dyn Error>> {
+        // <12> = facility user (1) * 8 + severity warning (4)
+        let message = format!(
+            "<12>simulated_embedded_system[{}]: run {}: {}",
+            std::process::id(),
+            run_id,
+            summarize(alerts)
+        );
+        self.socket.send(message.as_bytes())?;
+        Ok(())
+    }
+}
+
+// One line covering every alert of a batch
+fn summarize(alerts: &[String]) -> String {
+    match alerts {
+        [alert] => alert.clone(),
+        _ => format!("{} alerts: {}", alerts.len(), alerts.join("; ")),
+    }
+}
+
+// Sends alerts to the configured sinks their channels select. Alerts fired
+// within the grouping window of the first one go out together as a single
+// notification.
+pub struct Notifier {
+    run_id: String,
+    sinks: Vec<Box<dyn AlertSink>>,
+    group_window: Duration,
+    pending: Vec<(String, Vec<AlertChannel>)>,
+    window_start: Option<Instant>,
+}
+
+impl Notifier {
+    pub fn new(config: &NotificationConfig, run_id: &str) -> Self {
+        let mut sinks: Vec<Box<dyn AlertSink>> = Vec::new();
+        #[cfg(feature = "webhook")]
+        if let Some(url) = &config.webhook_url {
+            sinks.push(Box::new(WebhookSink { url: url.clone() }));
+        }
+        #[cfg(unix)]
+        if config.syslog {
+            match SyslogSink::new() {
+                Ok(sink) => sinks.push(Box::new(sink)),
+                Err(e) => println!("[WARNING]: Syslog notifications are unavailable: {}", e),
+            }
+        }
+        Notifier {
+            run_id: run_id.to_string(),
+            sinks,
+            group_window: Duration::from_secs(config.alert_group_window_secs),
+            pending: Vec::new(),
+            window_start: None,
+        }
+    }
+
+    pub fn notify(&mut self, alert: &str, channels: &[AlertChannel]) {
+        if !self
+            .sinks
+            .iter()
+            .any(|sink| channels.contains(&sink.channel()))
+        {
+            return;
+        }
+        self.pending.push((alert.to_string(), channels.to_vec()));
+        self.window_start.get_or_insert_with(Instant::now);
+        self.poll();
+    }
+
+    // Send the pending alerts once their grouping window has ended
+    pub fn poll(&mut self) {
+        if self
+            .window_start
+            .is_some_and(|start| start.elapsed() >= self.group_window)
+        {
+            self.flush();
+        }
+    }
+
+    // Send `alert` to every sink right away, whatever its channels, and
+    // report how each sink fared
+    pub fn send_now(&mut self, alert: &str) -> Vec<(String, Result<(), String>)> {
+        let alerts = [alert.to_string()];
+        self.sinks
+            .iter_mut()
+            .map(|sink| {
+                let result = sink.send(&self.run_id, &alerts);
+                (sink.name().to_string(), result.map_err(|e| e.to_string()))
+            })
+            .collect()
+    }
+
+    // Send the pending alerts now, e.g. at the end of a run
+    pub fn flush(&mut self) {
+        self.window_start = None;
+        if self.pending.is_empty() {
+            return;
+        }
+        let pending = std::mem::take(&mut self.pending);
+        for sink in &mut self.sinks {
+            let alerts: Vec<String> = pending
+                .iter()
+                .filter(|(_, channels)| channels.contains(&sink.channel()))
+                .map(|(alert, _)| alert.clone())
+                .collect();
+            if alerts.is_empty() {
+                continue;
+            }
+            // A failing sink must not stop the simulation
+            if let Err(e) = sink.send(&self.run_id, &alerts) {
+                println!(
+                    "[WARNING]: Failed to send {} alert(s) to {}: {}",
+                    alerts.len(),
+                    sink.name(),
+                    e
+                );
+            }
+        }
+    }
+}
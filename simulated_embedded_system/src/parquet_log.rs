@@ -0,0 +1,244 @@
+use crate::config::NanRepresentation;
+use crate::sensor::Reading;
+use arrow_array::{ArrayRef, BooleanArray, Float32Array, RecordBatch, TimestampMillisecondArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::NaiveDateTime;
+use parquet::arrow::ArrowWriter;
+use parquet::errors::ParquetError;
+use parquet::file::metadata::KeyValue;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::sync::Arc;
+
+// Rows buffered before they are written out as one row group
+const ROW_GROUP_SIZE: usize = 60;
+
+// Parquet log writer that batches rows into row groups
+pub struct ParquetWriter {
+    schema: SchemaRef,
+    writer: ArrowWriter<File>,
+    timestamps: Vec<i64>,
+    nan: NanRepresentation,
+    temperatures: Vec<Option<f32>>,
+    humidities: Vec<Option<f32>>,
+    lights: Vec<Option<f32>>,
+    pressures: Vec<Option<f32>>,
+    co2s: Vec<Option<f32>>,
+    // Unfiltered temperature, humidity and light with `log_raw_values = true`
+    raw: Option<[Vec<Option<f32>>; 3]>,
+    maintenance: Vec<bool>,
+    // A row group failed to write. The writer can't go on after that, so
+    // every later row is refused.
+    failed: bool,
+}
+
+impl ParquetWriter {
+    pub fn create(path: &str, nan: NanRepresentation, log_raw_values: bool) -> io::Result<Self> {
+        let mut fields = vec![
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+            Field::new("temperature", DataType::Float32, true),
+            Field::new("humidity", DataType::Float32, true),
+            Field::new("light", DataType::Float32, true),
+            // Null throughout when the optional sensor isn't configured
+            Field::new("pressure", DataType::Float32, true),
+            Field::new("co2", DataType::Float32, true),
+        ];
+        if log_raw_values {
+            for name in ["raw_temperature", "raw_humidity", "raw_light"] {
+                fields.push(Field::new(name, DataType::Float32, true));
+            }
+        }
+        fields.push(Field::new("maintenance", DataType::Boolean, false));
+        let schema = Arc::new(Schema::new(fields));
+        let file = File::create(path)?;
+        let writer =
+            ArrowWriter::try_new(file, Arc::clone(&schema), None).map_err(io::Error::other)?;
+        Ok(ParquetWriter {
+            schema,
+            writer,
+            timestamps: Vec::new(),
+            nan,
+            temperatures: Vec::new(),
+            humidities: Vec::new(),
+            lights: Vec::new(),
+            pressures: Vec::new(),
+            co2s: Vec::new(),
+            raw: log_raw_values.then(Default::default),
+            maintenance: Vec::new(),
+            failed: false,
+        })
+    }
+
+    pub fn write(
+        &mut self,
+        timestamp: NaiveDateTime,
+        reading: &Reading,
+        raw: &Reading,
+        maintenance: bool,
+    ) -> io::Result<()> {
+        if self.failed {
+            return Err(io::Error::other(
+                "an earlier row group could not be written",
+            ));
+        }
+        self.timestamps.push(timestamp.and_utc().timestamp_millis());
+        self.temperatures.push(self.value(reading.temperature));
+        self.humidities.push(self.value(reading.humidity));
+        self.lights.push(self.value(reading.light));
+        self.pressures
+            .push(reading.pressure.and_then(|value| self.value(value)));
+        self.co2s
+            .push(reading.co2.and_then(|value| self.value(value)));
+        let raw_values = [raw.temperature, raw.humidity, raw.light].map(|value| self.value(value));
+        if let Some(columns) = self.raw.as_mut() {
+            for (column, value) in columns.iter_mut().zip(raw_values) {
+                column.push(value);
+            }
+        }
+        self.maintenance.push(maintenance);
+
+        if self.timestamps.len() >= ROW_GROUP_SIZE {
+            let result = self.flush();
+            self.failed = result.is_err();
+            result?;
+        }
+        Ok(())
+    }
+
+    // Rows buffered for the next row group; a failed write loses them
+    pub fn buffered_rows(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    // Record the run ID in the file's key-value metadata, and each tag as `tag.<key>`
+    pub fn set_run_metadata(&mut self, run_id: &str, tags: &BTreeMap<String, String>) {
+        self.writer
+            .append_key_value_metadata(KeyValue::new("run_id".to_string(), run_id.to_string()));
+        for (key, value) in tags {
+            self.writer
+                .append_key_value_metadata(KeyValue::new(format!("tag.{}", key), value.clone()));
+        }
+    }
+
+    // Column value for a reading: NaN and infinite values become null with
+    // `nan_representation = "empty"`, or the sentinel when one is configured
+    fn value(&self, value: f32) -> Option<f32> {
+        if value.is_finite() {
+            return Some(value);
+        }
+        match self.nan {
+            NanRepresentation::Nan => Some(f32::NAN),
+            NanRepresentation::Empty => None,
+            NanRepresentation::Sentinel(sentinel) => Some(sentinel),
+        }
+    }
+
+    // Write the buffered rows as a row group
+    fn flush(&mut self) -> io::Result<()> {
+        if self.timestamps.is_empty() {
+            return Ok(());
+        }
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(TimestampMillisecondArray::from(std::mem::take(
+                &mut self.timestamps,
+            ))),
+            Arc::new(Float32Array::from(std::mem::take(&mut self.temperatures))),
+            Arc::new(Float32Array::from(std::mem::take(&mut self.humidities))),
+            Arc::new(Float32Array::from(std::mem::take(&mut self.lights))),
+            Arc::new(Float32Array::from(std::mem::take(&mut self.pressures))),
+            Arc::new(Float32Array::from(std::mem::take(&mut self.co2s))),
+        ];
+        if let Some(raw) = self.raw.as_mut() {
+            for column in raw {
+                columns.push(Arc::new(Float32Array::from(std::mem::take(column))));
+            }
+        }
+        columns.push(Arc::new(BooleanArray::from(std::mem::take(
+            &mut self.maintenance,
+        ))));
+        let batch =
+            RecordBatch::try_new(Arc::clone(&self.schema), columns).map_err(io::Error::other)?;
+        self.writer.write(&batch).map_err(io_error)?;
+        self.writer.flush().map_err(io_error)
+    }
+
+    // Flush the remaining rows and write the file footer. A file whose writes
+    // failed can't be completed; its rows were already counted as lost.
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.failed {
+            return Ok(());
+        }
+        self.flush()?;
+        self.writer.close().map_err(io_error)?;
+        Ok(())
+    }
+}
+
+// The I/O error behind a Parquet error, so a full disk is recognized as such
+fn io_error(e: ParquetError) -> io::Error {
+    match e {
+        ParquetError::External(e) => match e.downcast::<io::Error>() {
+            Ok(e) => *e,
+            Err(e) => io::Error::other(e),
+        },
+        e => io::Error::other(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn raw_values_follow_the_readings() {
+        let path = std::env::temp_dir().join(format!("raw_values_{}.parquet", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let mut writer = ParquetWriter::create(&path, NanRepresentation::Empty, true).unwrap();
+        let reading = Reading {
+            temperature: 21.5,
+            humidity: 45.25,
+            light: 80.0,
+            pressure: None,
+            co2: None,
+        };
+        let raw = Reading {
+            temperature: 23.0,
+            humidity: f32::NAN,
+            ..reading
+        };
+        let timestamp =
+            NaiveDateTime::parse_from_str("2024-05-01 15:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        writer.write(timestamp, &reading, &raw, false).unwrap();
+        writer.finish().unwrap();
+
+        let file = File::open(&path).unwrap();
+        let batch = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let column = |name: &str| {
+            batch
+                .column_by_name(name)
+                .unwrap()
+                .as_any()
+                .downcast_ref::<Float32Array>()
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(column("temperature").value(0), 21.5);
+        assert_eq!(column("raw_temperature").value(0), 23.0);
+        assert!(column("raw_humidity").is_null(0));
+        assert_eq!(column("raw_light").value(0), 80.0);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::alerts::{Alert, AlertManager};
+use crate::config::{AlertChannel, AlertConfig, Config, NanRepresentation};
+use crate::display::{ConsoleDisplay, Display};
+use crate::logger::{
+    is_alert_line, is_dropout_line, is_run_line, parse_log_line, TIMESTAMP_FORMAT,
+};
+use crate::stats::RollingWindow;
+use chrono::NaiveDateTime;
+use std::error::Error;
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Options for replaying a recorded log
+pub struct ReplayOptions {
+    // Only rows with `from <= timestamp <= to` are replayed
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+    // Playback speed relative to the original timing
+    pub speed: f64,
+}
+
+// Clock following a log's timestamps, so alert debouncing sees the recorded
+// timing however fast the log is replayed
+struct LogClock {
+    start: Instant,
+    first: Option<NaiveDateTime>,
+}
+
+impl LogClock {
+    fn new() -> Self {
+        LogClock {
+            start: Instant::now(),
+            first: None,
+        }
+    }
+
+    fn at(&mut self, timestamp: NaiveDateTime) -> Instant {
+        let first = *self.first.get_or_insert(timestamp);
+        self.start + (timestamp - first).to_std().unwrap_or(Duration::ZERO)
+    }
+}
+
+// Alerts raised by the readings of a recorded log, with the timestamp of the
+// row that raised them. Timed by the log itself, so the result is the same
+// on every run.
+pub fn replay_alerts(
+    logs: &str,
+    alerts: &AlertConfig,
+    nan: NanRepresentation,
+) -> Vec<(NaiveDateTime, Alert)> {
+    let mut alert_manager = AlertManager::default();
+    let mut clock = LogClock::new();
+    let mut raised = Vec::new();
+    for line in logs.lines() {
+        if is_alert_line(line) || is_dropout_line(line) || is_run_line(line) {
+            continue;
+        }
+        let Some((timestamp, reading)) = parse_log_line(line, nan) else {
+            continue;
+        };
+        let now = clock.at(timestamp);
+        for alert in alert_manager.check(&reading, alerts, now) {
+            raised.push((timestamp, alert));
+        }
+    }
+    raised
+}
+
+// Replay a recorded log through the display and alerts, keeping its original pacing
+pub fn replay_log(
+    log_path: &str,
+    config: &Config,
+    options: &ReplayOptions,
+) -> Result<(), Box<dyn Error>> {
+    if !(options.speed.is_finite() && options.speed > 0.0) {
+        return Err(format!("Replay speed must be positive, got {}", options.speed).into());
+    }
+
+    let logs = fs::read_to_string(log_path)?;
+    let mut display = ConsoleDisplay::new();
+    display.partial_window = config.display.partial_window;
+    let mut temperature_window =
+        RollingWindow::new(config.display.window_size(&config.display.temperature));
+    let unit = config.display.temperature_unit;
+    let mut alert_manager = AlertManager::new(unit);
+    let mut clock = LogClock::new();
+    let mut previous_timestamp: Option<NaiveDateTime> = None;
+    let mut replayed: u64 = 0;
+    let nan = config.storage.nan_representation;
+
+    println!("Replaying {}...", log_path);
+    for (index, line) in logs.lines().enumerate() {
+        // Alerts are re-evaluated from the readings
+        if line.trim().is_empty()
+            || is_alert_line(line)
+            || is_dropout_line(line)
+            || is_run_line(line)
+        {
+            continue;
+        }
+        let Some((timestamp, reading)) = parse_log_line(line, nan) else {
+            println!(
+                "[WARNING]: Skipping unparseable line {}: {}",
+                index + 1,
+                line
+            );
+            continue;
+        };
+        if options.from.is_some_and(|from| timestamp < from) {
+            continue;
+        }
+        if options.to.is_some_and(|to| timestamp > to) {
+            continue;
+        }
+
+        // Wait as long as the original recording did between rows
+        if let Some(previous) = previous_timestamp {
+            if let Ok(gap) = (timestamp - previous).to_std() {
+                thread::sleep(gap.div_f64(options.speed));
+            }
+        }
+        previous_timestamp = Some(timestamp);
+        replayed += 1;
+
+        display.show(&format!(
+            "[{}] Temperature: {:.2}{}, Humidity: {:.2}%, Light: {:.2}%",
+            timestamp.format(TIMESTAMP_FORMAT),
+            reading.temperature,
+            unit.symbol(),
+            reading.humidity,
+            reading.light
+        ));
+
+        temperature_window.push(reading.temperature);
+        if config.display.real_time_graph {
+            display.show_graph(
+                "Temperature",
+                temperature_window.values(),
+                temperature_window.size(),
+                &config.display.temperature,
+            );
+        }
+
+        let alerts = alert_manager.check(&reading, &config.alerts, clock.at(timestamp));
+        for alert in alerts {
+            if config
+                .alerts
+                .channels(alert.sensor)
+                .contains(&AlertChannel::Console)
+            {
+                display.show_alert(&alert.message);
+            }
+        }
+    }
+
+    println!("Replay completed: {} row(s) replayed.", replayed);
+    Ok(())
+}
+
+// Parse a replay window bound given on the command line
+pub fn parse_timestamp(value: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(value, TIMESTAMP_FORMAT)
+        .map_err(|e| format!("expected \"YYYY-MM-DD HH:MM:SS\": {}", e))
+}
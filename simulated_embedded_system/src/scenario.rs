@@ -0,0 +1,91 @@
+use crate::bus::Channel;
+use serde::Deserialize;
+use std::error::Error;
+use std::fs;
+use std::time::Duration;
+
+// Scenarios shipped with the binary, selectable by name
+pub const BUILTIN_SCENARIOS: &[(&str, &str)] = &[
+    ("stable", include_str!("../scenarios/stable.toml")),
+    ("heatwave", include_str!("../scenarios/heatwave.toml")),
+    (
+        "sensor-failure",
+        include_str!("../scenarios/sensor-failure.toml"),
+    ),
+];
+
+// A scripted sequence of sensor conditions played back during a run
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub description: String,
+    #[serde(rename = "phase")]
+    pub phases: Vec<Phase>,
+}
+
+// Conditions for part of a scenario. Sensors without a range read as usual;
+// failed sensors read NaN.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Phase {
+    pub duration_secs: u64,
+    pub temperature: Option<(f32, f32)>,
+    pub humidity: Option<(f32, f32)>,
+    pub light: Option<(f32, f32)>,
+    #[serde(default)]
+    pub failed: Vec<Channel>,
+}
+
+impl Scenario {
+    // Load a built-in scenario by name, or a scenario file
+    pub fn load(name_or_path: &str) -> Result<Self, Box<dyn Error>> {
+        let builtin = BUILTIN_SCENARIOS
+            .iter()
+            .find(|(name, _)| *name == name_or_path);
+        match builtin {
+            Some((_, scenario_str)) => Scenario::parse(scenario_str),
+            None => Scenario::parse(&fs::read_to_string(name_or_path)?),
+        }
+    }
+
+    pub fn parse(scenario_str: &str) -> Result<Self, Box<dyn Error>> {
+        let scenario: Scenario = toml::from_str(scenario_str)?;
+        if scenario.phases.is_empty() {
+            return Err("Scenario has no phases".into());
+        }
+        for phase in &scenario.phases {
+            for (min, max) in [phase.temperature, phase.humidity, phase.light]
+                .into_iter()
+                .flatten()
+            {
+                if !(min.is_finite() && max.is_finite() && min <= max) {
+                    return Err(format!("Invalid scenario range [{}, {}]", min, max).into());
+                }
+            }
+        }
+        Ok(scenario)
+    }
+
+    // Index and phase in effect `elapsed` into the run; the last phase lasts
+    // until the run ends
+    pub fn phase_at(&self, elapsed: Duration) -> (usize, &Phase) {
+        let mut end = Duration::ZERO;
+        for (index, phase) in self.phases.iter().enumerate() {
+            end += Duration::from_secs(phase.duration_secs);
+            if elapsed < end {
+                return (index, phase);
+            }
+        }
+        let last = self.phases.len() - 1;
+        (last, &self.phases[last])
+    }
+}
+
+// Print the built-in scenarios
+pub fn list_scenarios() -> Result<(), Box<dyn Error>> {
+    println!("=== Built-in Scenarios ===");
+    for (name, scenario_str) in BUILTIN_SCENARIOS {
+        let scenario = Scenario::parse(scenario_str)?;
+        println!("{:<16} {}", name, scenario.description);
+    }
+    Ok(())
+}
@@ -0,0 +1,711 @@
+use crate::bus::Channel;
+use crate::config::{
+    NanRepresentation, SensorConfig, SensorModel, SensorSource, TemperatureUnit, TransientConfig,
+};
+use crate::logger::parse_log_line;
+use crate::scenario::Phase;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::error::Error;
+use std::f32::consts::PI;
+use std::fs;
+use std::ops::Range;
+#[cfg(feature = "system-metrics")]
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+#[cfg(feature = "system-metrics")]
+use std::thread;
+use std::time::{Duration, Instant};
+
+// A sensor sampled by the simulation
+pub trait Sensor {
+    fn name(&self) -> &str;
+    fn unit(&self) -> &str;
+    // Take a reading; simulated sensors advance their state with each one
+    fn read(&mut self) -> f32;
+    fn sampling_rate(&self) -> Duration;
+    // ADC channel the sensor is wired to
+    fn channel(&self) -> Channel;
+
+    // Follow a scenario phase; sensors without simulated values ignore it
+    fn set_phase(&mut self, _phase: &Phase) {}
+
+    // Reads abandoned after the source's timeout since the last call
+    fn take_timeouts(&mut self) -> u64 {
+        0
+    }
+
+    // Whether a sensor replaying recorded values has none left
+    fn exhausted(&self) -> bool {
+        false
+    }
+}
+
+// The sensors configured under `[sensors]`, in display order
+pub fn from_config(config: &SensorConfig) -> Vec<Box<dyn Sensor>> {
+    let mut sensors: Vec<Box<dyn Sensor>> = vec![
+        Box::new(TemperatureSensor::new(config)),
+        Box::new(HumiditySensor::new(config)),
+        Box::new(LightSensor::new(config)),
+    ];
+    if let Some(sensor) = PressureSensor::new(config) {
+        sensors.push(Box::new(sensor));
+    }
+    if let Some(sensor) = Co2Sensor::new(config) {
+        sensors.push(Box::new(sensor));
+    }
+    sensors
+}
+
+// The sensors configured under `[sensors]`, with the core three replaying the
+// readings of the text or CSV log at `path`, whose temperatures are in `unit`.
+// Pressure and CO2 stay simulated.
+pub fn replay_from_log(
+    config: &SensorConfig,
+    path: &str,
+    nan: NanRepresentation,
+    unit: TemperatureUnit,
+) -> Result<Vec<Box<dyn Sensor>>, Box<dyn Error>> {
+    let logs = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read replay file {}: {}", path, e))?;
+    let readings: Vec<Reading> = logs
+        .lines()
+        .filter_map(|line| parse_log_line(line, nan))
+        .map(|(_, reading)| reading)
+        .collect();
+    if readings.is_empty() {
+        return Err(format!("No readings found in replay file {}", path).into());
+    }
+
+    let mut sensors = from_config(config);
+    for sensor in &mut sensors {
+        let values = match sensor.channel() {
+            Channel::Temperature => readings
+                .iter()
+                .map(|r| unit.to_celsius(r.temperature))
+                .collect(),
+            Channel::Humidity => readings.iter().map(|r| r.humidity).collect(),
+            Channel::Light => readings.iter().map(|r| r.light).collect(),
+            Channel::Pressure | Channel::Co2 => continue,
+        };
+        *sensor = Box::new(ReplaySensor {
+            name: sensor.name().to_string(),
+            unit: sensor.unit().to_string(),
+            channel: sensor.channel(),
+            sampling_rate: sensor.sampling_rate(),
+            values: Vec::into_iter(values),
+        });
+    }
+    Ok(sensors)
+}
+
+// Simulated values of one sensor: scripted by the scenario phase, echoed from
+// a host metric, or random within `range`, with the power-on transient applied
+struct Simulated {
+    channel: Channel,
+    range: Range<f32>,
+    model: Option<SensorModel>,
+    // When the sensor was created, for the model's drift
+    started: Instant,
+    transient: Transient,
+    phase: Option<Phase>,
+    rng: StdRng,
+    #[cfg(feature = "system-metrics")]
+    source: SensorSource,
+    // The host metric turned out to be unavailable
+    #[cfg(feature = "system-metrics")]
+    unavailable: bool,
+    // A host metric read that timed out and may still complete
+    #[cfg(feature = "system-metrics")]
+    in_flight: Option<Receiver<Option<f32>>>,
+    #[cfg(feature = "system-metrics")]
+    last_value: f32,
+    #[cfg(feature = "system-metrics")]
+    timeouts: u64,
+}
+
+impl Simulated {
+    // With a `seed` the values repeat exactly from run to run. Each channel
+    // gets its own stream, so the sensors don't mirror each other.
+    fn new(
+        channel: Channel,
+        range: Range<f32>,
+        model: Option<&SensorModel>,
+        transient: &TransientConfig,
+        _source: SensorSource,
+        seed: Option<u64>,
+    ) -> Self {
+        Simulated {
+            channel,
+            range,
+            model: model.cloned(),
+            started: Instant::now(),
+            transient: Transient::new(transient),
+            phase: None,
+            rng: match seed {
+                Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(channel as u64)),
+                None => StdRng::from_entropy(),
+            },
+            #[cfg(feature = "system-metrics")]
+            source: _source,
+            #[cfg(feature = "system-metrics")]
+            unavailable: false,
+            #[cfg(feature = "system-metrics")]
+            in_flight: None,
+            #[cfg(feature = "system-metrics")]
+            last_value: f32::NAN,
+            #[cfg(feature = "system-metrics")]
+            timeouts: 0,
+        }
+    }
+
+    fn read(&mut self) -> f32 {
+        let value = self
+            .scripted()
+            .or_else(|| self.host_metric())
+            .unwrap_or_else(|| self.modelled());
+        self.transient.apply(value, &mut self.rng)
+    }
+
+    // Value from the sensor's model, or uniform over its range without one
+    fn modelled(&mut self) -> f32 {
+        let Some(model) = &self.model else {
+            return self.rng.gen_range(self.range.clone());
+        };
+        let minutes = self.started.elapsed().as_secs_f32() / 60.0;
+        let noise = if model.noise_stddev > 0.0 {
+            // Box-Muller transform of two uniform samples
+            let u1: f32 = self.rng.gen_range(f32::EPSILON..1.0);
+            let u2: f32 = self.rng.gen();
+            model.noise_stddev * (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+        } else {
+            0.0
+        };
+        model.baseline + model.drift_per_minute * minutes + noise
+    }
+
+    // Value from the scenario phase, if it overrides this sensor
+    fn scripted(&mut self) -> Option<f32> {
+        let phase = self.phase.as_ref()?;
+        if phase.failed.contains(&self.channel) {
+            return Some(f32::NAN);
+        }
+        let (min, max) = match self.channel {
+            Channel::Temperature => phase.temperature?,
+            Channel::Humidity => phase.humidity?,
+            Channel::Light => phase.light?,
+            Channel::Pressure | Channel::Co2 => return None,
+        };
+        Some(self.rng.gen_range(min..=max))
+    }
+
+    // Value of the host metric this sensor echoes, if it has one. A metric the
+    // host doesn't expose falls back to simulated values, with a warning.
+    //
+    // The metric is read on a separate thread. A read that outlasts
+    // `read_timeout_ms` is counted as a timeout and the last value is kept;
+    // the next read waits on the same thread rather than starting another.
+    #[cfg(feature = "system-metrics")]
+    fn host_metric(&mut self) -> Option<f32> {
+        let SensorSource::System {
+            metric,
+            read_timeout_ms,
+        } = self.source
+        else {
+            return None;
+        };
+        if self.unavailable {
+            return None;
+        }
+        let read = self.in_flight.take().unwrap_or_else(|| {
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                let _ = sender.send(crate::system_metrics::read(metric));
+            });
+            receiver
+        });
+        match read.recv_timeout(Duration::from_millis(read_timeout_ms)) {
+            Ok(Some(value)) => {
+                self.last_value = value;
+                Some(value)
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                println!(
+                    "[WARNING]: Reading host metric {:?} timed out after {} ms; keeping the last {:?} value",
+                    metric, read_timeout_ms, self.channel
+                );
+                self.in_flight = Some(read);
+                self.timeouts += 1;
+                Some(self.last_value)
+            }
+            Ok(None) | Err(RecvTimeoutError::Disconnected) => {
+                println!(
+                    "[WARNING]: Host metric {:?} is not available; simulating the {:?} sensor instead",
+                    metric, self.channel
+                );
+                self.unavailable = true;
+                None
+            }
+        }
+    }
+
+    #[cfg(not(feature = "system-metrics"))]
+    fn host_metric(&mut self) -> Option<f32> {
+        None
+    }
+
+    #[cfg(feature = "system-metrics")]
+    fn take_timeouts(&mut self) -> u64 {
+        std::mem::take(&mut self.timeouts)
+    }
+
+    #[cfg(not(feature = "system-metrics"))]
+    fn take_timeouts(&mut self) -> u64 {
+        0
+    }
+}
+
+// Simulated temperature sensor, 20°C to 30°C
+pub struct TemperatureSensor {
+    simulated: Simulated,
+    sampling_rate: Duration,
+}
+
+impl TemperatureSensor {
+    pub fn new(config: &SensorConfig) -> Self {
+        TemperatureSensor {
+            simulated: Simulated::new(
+                Channel::Temperature,
+                20.0..30.0,
+                config.temperature_model.as_ref(),
+                &config.temperature_transient,
+                config.temperature_source,
+                config.seed,
+            ),
+            sampling_rate: config
+                .sampling_rate(Channel::Temperature)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl Sensor for TemperatureSensor {
+    fn name(&self) -> &str {
+        "Temperature"
+    }
+
+    fn unit(&self) -> &str {
+        "°C"
+    }
+
+    fn read(&mut self) -> f32 {
+        self.simulated.read()
+    }
+
+    fn sampling_rate(&self) -> Duration {
+        self.sampling_rate
+    }
+
+    fn channel(&self) -> Channel {
+        Channel::Temperature
+    }
+
+    fn set_phase(&mut self, phase: &Phase) {
+        self.simulated.phase = Some(phase.clone());
+    }
+
+    fn take_timeouts(&mut self) -> u64 {
+        self.simulated.take_timeouts()
+    }
+}
+
+// Simulated humidity sensor, 30% to 70%
+pub struct HumiditySensor {
+    simulated: Simulated,
+    sampling_rate: Duration,
+}
+
+impl HumiditySensor {
+    pub fn new(config: &SensorConfig) -> Self {
+        HumiditySensor {
+            simulated: Simulated::new(
+                Channel::Humidity,
+                30.0..70.0,
+                config.humidity_model.as_ref(),
+                &config.humidity_transient,
+                config.humidity_source,
+                config.seed,
+            ),
+            sampling_rate: config.sampling_rate(Channel::Humidity).unwrap_or_default(),
+        }
+    }
+}
+
+impl Sensor for HumiditySensor {
+    fn name(&self) -> &str {
+        "Humidity"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+
+    fn read(&mut self) -> f32 {
+        self.simulated.read()
+    }
+
+    fn sampling_rate(&self) -> Duration {
+        self.sampling_rate
+    }
+
+    fn channel(&self) -> Channel {
+        Channel::Humidity
+    }
+
+    fn set_phase(&mut self, phase: &Phase) {
+        self.simulated.phase = Some(phase.clone());
+    }
+
+    fn take_timeouts(&mut self) -> u64 {
+        self.simulated.take_timeouts()
+    }
+}
+
+// Simulated light intensity sensor, 0% to 100%
+pub struct LightSensor {
+    simulated: Simulated,
+    sampling_rate: Duration,
+}
+
+impl LightSensor {
+    pub fn new(config: &SensorConfig) -> Self {
+        LightSensor {
+            simulated: Simulated::new(
+                Channel::Light,
+                0.0..100.0,
+                config.light_model.as_ref(),
+                &config.light_transient,
+                config.light_source,
+                config.seed,
+            ),
+            sampling_rate: config.sampling_rate(Channel::Light).unwrap_or_default(),
+        }
+    }
+}
+
+impl Sensor for LightSensor {
+    fn name(&self) -> &str {
+        "Light"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+
+    fn read(&mut self) -> f32 {
+        self.simulated.read()
+    }
+
+    fn sampling_rate(&self) -> Duration {
+        self.sampling_rate
+    }
+
+    fn channel(&self) -> Channel {
+        Channel::Light
+    }
+
+    fn set_phase(&mut self, phase: &Phase) {
+        self.simulated.phase = Some(phase.clone());
+    }
+
+    fn take_timeouts(&mut self) -> u64 {
+        self.simulated.take_timeouts()
+    }
+}
+
+// Simulated barometric pressure sensor, 950 hPa to 1050 hPa
+pub struct PressureSensor {
+    simulated: Simulated,
+    sampling_rate: Duration,
+}
+
+impl PressureSensor {
+    // `None` unless `pressure_sampling_rate` or its `_ms` form is configured
+    pub fn new(config: &SensorConfig) -> Option<Self> {
+        Some(PressureSensor {
+            simulated: Simulated::new(
+                Channel::Pressure,
+                950.0..1050.0,
+                None,
+                &TransientConfig::default(),
+                SensorSource::Virtual,
+                config.seed,
+            ),
+            sampling_rate: config.sampling_rate(Channel::Pressure)?,
+        })
+    }
+}
+
+impl Sensor for PressureSensor {
+    fn name(&self) -> &str {
+        "Pressure"
+    }
+
+    fn unit(&self) -> &str {
+        "hPa"
+    }
+
+    fn read(&mut self) -> f32 {
+        self.simulated.read()
+    }
+
+    fn sampling_rate(&self) -> Duration {
+        self.sampling_rate
+    }
+
+    fn channel(&self) -> Channel {
+        Channel::Pressure
+    }
+
+    fn set_phase(&mut self, phase: &Phase) {
+        self.simulated.phase = Some(phase.clone());
+    }
+}
+
+// Simulated CO2 sensor, 400 ppm to 2000 ppm
+pub struct Co2Sensor {
+    simulated: Simulated,
+    sampling_rate: Duration,
+}
+
+impl Co2Sensor {
+    // `None` unless `co2_sampling_rate` or its `_ms` form is configured
+    pub fn new(config: &SensorConfig) -> Option<Self> {
+        Some(Co2Sensor {
+            simulated: Simulated::new(
+                Channel::Co2,
+                400.0..2000.0,
+                None,
+                &TransientConfig::default(),
+                SensorSource::Virtual,
+                config.seed,
+            ),
+            sampling_rate: config.sampling_rate(Channel::Co2)?,
+        })
+    }
+}
+
+impl Sensor for Co2Sensor {
+    fn name(&self) -> &str {
+        "CO2"
+    }
+
+    fn unit(&self) -> &str {
+        "ppm"
+    }
+
+    fn read(&mut self) -> f32 {
+        self.simulated.read()
+    }
+
+    fn sampling_rate(&self) -> Duration {
+        self.sampling_rate
+    }
+
+    fn channel(&self) -> Channel {
+        Channel::Co2
+    }
+
+    fn set_phase(&mut self, phase: &Phase) {
+        self.simulated.phase = Some(phase.clone());
+    }
+}
+
+// Returns the values recorded for one sensor in order, one per sample
+pub struct ReplaySensor {
+    name: String,
+    unit: String,
+    channel: Channel,
+    sampling_rate: Duration,
+    values: std::vec::IntoIter<f32>,
+}
+
+impl Sensor for ReplaySensor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn unit(&self) -> &str {
+        &self.unit
+    }
+
+    // NaN once the recorded values are used up
+    fn read(&mut self) -> f32 {
+        self.values.next().unwrap_or(f32::NAN)
+    }
+
+    fn sampling_rate(&self) -> Duration {
+        self.sampling_rate
+    }
+
+    fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    fn exhausted(&self) -> bool {
+        self.values.len() == 0
+    }
+}
+
+// Check that `source` can deliver readings on this host before it is selected
+pub fn check_source(source: SensorSource) -> Result<(), String> {
+    match source {
+        SensorSource::Virtual => Ok(()),
+        #[cfg(feature = "system-metrics")]
+        SensorSource::System { metric, .. } => crate::system_metrics::read(metric)
+            .map(|_| ())
+            .ok_or_else(|| format!("{} is not available on this host", source)),
+        #[cfg(not(feature = "system-metrics"))]
+        SensorSource::System { .. } => Err(format!(
+            "{} requires building with `--features system-metrics`",
+            source
+        )),
+    }
+}
+
+// Startup error of one sensor, decaying with each sample taken
+#[derive(Default)]
+struct Transient {
+    config: TransientConfig,
+    samples_taken: u32,
+}
+
+impl Transient {
+    fn new(config: &TransientConfig) -> Self {
+        Transient {
+            config: config.clone(),
+            samples_taken: 0,
+        }
+    }
+
+    fn apply(&mut self, value: f32, rng: &mut StdRng) -> f32 {
+        if self.samples_taken >= self.config.samples {
+            return value;
+        }
+        let strength = 1.0 - self.samples_taken as f32 / self.config.samples as f32;
+        self.samples_taken += 1;
+
+        let mut error = self.config.offset;
+        if self.config.noise > 0.0 {
+            error += rng.gen_range(-self.config.noise..=self.config.noise);
+        }
+        value + error * strength
+    }
+}
+
+// A complete set of sensor readings taken in the same tick. The optional
+// sensors are `None` when not configured, and carry their latest reading.
+#[derive(Debug, Clone, Copy)]
+pub struct Reading {
+    pub temperature: f32,
+    pub humidity: f32,
+    pub light: f32,
+    pub pressure: Option<f32>,
+    pub co2: Option<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn readings(seed: u64) -> Vec<f32> {
+        let config: SensorConfig = toml::from_str(&format!(
+            "temperature_sampling_rate = 1
+             humidity_sampling_rate = 1
+             light_sampling_rate = 1
+             seed = {}
+             [temperature_transient]
+             samples = 5
+             noise = 1.0",
+            seed
+        ))
+        .unwrap();
+        let mut sensors = from_config(&config);
+        let mut values = Vec::new();
+        for _ in 0..20 {
+            for sensor in &mut sensors {
+                values.push(sensor.read());
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn seeded_sensors_repeat_their_readings() {
+        assert_eq!(readings(42), readings(42));
+        assert_ne!(readings(42), readings(43));
+    }
+
+    #[test]
+    fn modelled_values_center_on_the_baseline() {
+        let config: SensorConfig = toml::from_str(
+            "temperature_sampling_rate = 1
+             humidity_sampling_rate = 1
+             light_sampling_rate = 1
+             seed = 7
+             [temperature_model]
+             baseline = 22.0
+             noise_stddev = 0.5",
+        )
+        .unwrap();
+        let mut sensor = TemperatureSensor::new(&config);
+        let values: Vec<f32> = (0..5000).map(|_| sensor.read()).collect();
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        let variance = values
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f32>()
+            / values.len() as f32;
+        assert!((mean - 22.0).abs() < 0.05, "mean {}", mean);
+        assert!(
+            (variance.sqrt() - 0.5).abs() < 0.05,
+            "stddev {}",
+            variance.sqrt()
+        );
+    }
+
+    #[test]
+    fn replayed_sensors_return_the_logged_readings() {
+        let path = std::env::temp_dir().join(format!("replay_{}.txt", std::process::id()));
+        fs::write(
+            &path,
+            "2024-05-01 15:00:00, RUN, 20240501-150000-3fa2
+2024-05-01 15:00:00, 21.50, 45.00, 80.00
+2024-05-01 15:00:01, ALERT, Light intensity exceeded threshold: 81.00%
+2024-05-01 15:00:01, 22.00, 46.00, 81.00
+",
+        )
+        .unwrap();
+        let config: SensorConfig = toml::from_str(
+            "temperature_sampling_rate = 1
+             humidity_sampling_rate = 1
+             light_sampling_rate = 1",
+        )
+        .unwrap();
+        let sensors = replay_from_log(
+            &config,
+            &path.to_string_lossy(),
+            NanRepresentation::default(),
+            TemperatureUnit::Celsius,
+        );
+        fs::remove_file(&path).unwrap();
+
+        let mut sensors = sensors.unwrap();
+        let mut values = Vec::new();
+        while !sensors.iter().any(|sensor| sensor.exhausted()) {
+            for sensor in &mut sensors {
+                values.push(sensor.read());
+            }
+        }
+        assert_eq!(values, [21.5, 45.0, 80.0, 22.0, 46.0, 81.0]);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::history::SharedHistory;
+use crate::sensor::Reading;
+use crate::stats::{calculate_statistics, percentile};
+use serde::Serialize;
+use std::error::Error;
+use std::thread;
+use tiny_http::{Header, Response, Server};
+
+// Aggregate statistics of one sensor over the requested window
+#[derive(Serialize)]
+struct SensorAggregate {
+    min: f32,
+    mean: f32,
+    max: f32,
+    p50: f32,
+    p90: f32,
+    p95: f32,
+    p99: f32,
+}
+
+#[derive(Serialize)]
+struct AggregateResponse {
+    window: usize,
+    samples: usize,
+    temperature: Option<SensorAggregate>,
+    humidity: Option<SensorAggregate>,
+    light: Option<SensorAggregate>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+// Start serving `/aggregate` on a background thread
+pub fn spawn(port: u16, history: SharedHistory) -> Result<(), Box<dyn Error>> {
+    let server = Server::http(("0.0.0.0", port)).map_err(|e| e.to_string())?;
+    println!("[HTTP Server]: Listening on port {}", port);
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let (status, body) = handle(request.url(), &history);
+            let header = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                .expect("static header is valid");
+            let response = Response::from_string(body)
+                .with_status_code(status)
+                .with_header(header);
+            // A client hanging up early is not our problem
+            let _ = request.respond(response);
+        }
+    });
+
+    Ok(())
+}
+
+// Route a request URL to its handler, returning the status code and JSON body
+fn handle(url: &str, history: &SharedHistory) -> (u16, String) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    match path {
+        "/aggregate" => match parse_window(query) {
+            Ok(window) => (200, aggregate(window, history)),
+            Err(message) => (400, error_body(&message)),
+        },
+        _ => (404, error_body("not found")),
+    }
+}
+
+// Read the `window` query parameter; `None` means the whole buffer
+fn parse_window(query: &str) -> Result<Option<usize>, String> {
+    let value = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == "window")
+        .map(|(_, value)| value);
+
+    match value {
+        None => Ok(None),
+        Some(value) => match value.parse::<usize>() {
+            Ok(0) | Err(_) => Err(format!(
+                "window must be a positive number of samples, got '{}'",
+                value
+            )),
+            Ok(window) => Ok(Some(window)),
+        },
+    }
+}
+
+// Compute the aggregate over the most recent `window` readings on demand
+fn aggregate(window: Option<usize>, history: &SharedHistory) -> String {
+    let buffer = history.lock().unwrap_or_else(|e| e.into_inner());
+    let window = window.unwrap_or(buffer.capacity).min(buffer.capacity);
+    let readings: Vec<Reading> = buffer.recent(window).copied().collect();
+    drop(buffer);
+
+    let response = AggregateResponse {
+        window,
+        samples: readings.len(),
+        temperature: sensor_aggregate(readings.iter().map(|r| r.temperature).collect()),
+        humidity: sensor_aggregate(readings.iter().map(|r| r.humidity).collect()),
+        light: sensor_aggregate(readings.iter().map(|r| r.light).collect()),
+    };
+    serde_json::to_string(&response).expect("aggregate response serializes")
+}
+
+fn sensor_aggregate(mut values: Vec<f32>) -> Option<SensorAggregate> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f32::total_cmp);
+    let stats = calculate_statistics(&values)?;
+    Some(SensorAggregate {
+        min: stats.min,
+        mean: stats.average,
+        max: stats.max,
+        p50: percentile(&values, 50.0)?,
+        p90: percentile(&values, 90.0)?,
+        p95: percentile(&values, 95.0)?,
+        p99: percentile(&values, 99.0)?,
+    })
+}
+
+fn error_body(message: &str) -> String {
+    serde_json::to_string(&ErrorResponse {
+        error: message.to_string(),
+    })
+    .expect("error response serializes")
+}
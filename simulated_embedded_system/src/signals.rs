@@ -0,0 +1,94 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(unix)]
+use std::sync::OnceLock;
+
+#[cfg(unix)]
+pub use signal_hook::consts::{SIGHUP, SIGINT, SIGUSR1};
+// Placeholders where Unix signals don't exist; flags for them are never raised
+#[cfg(not(unix))]
+pub const SIGHUP: i32 = 1;
+#[cfg(not(unix))]
+pub const SIGUSR1: i32 = 10;
+#[cfg(not(unix))]
+pub const SIGINT: i32 = 2;
+
+// Flag raised whenever the process receives a signal, checked by the run loop.
+// The handler is removed again when the flag is dropped.
+pub struct SignalFlag {
+    raised: Arc<AtomicBool>,
+    #[cfg(unix)]
+    signal_id: signal_hook::SigId,
+}
+
+impl SignalFlag {
+    #[cfg(unix)]
+    pub fn register(signal: i32) -> io::Result<Self> {
+        let raised = Arc::new(AtomicBool::new(false));
+        let signal_id = signal_hook::flag::register(signal, Arc::clone(&raised))?;
+        Ok(SignalFlag { raised, signal_id })
+    }
+
+    #[cfg(not(unix))]
+    pub fn register(_signal: i32) -> io::Result<Self> {
+        Ok(SignalFlag {
+            raised: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    // Whether the signal arrived since the last call
+    pub fn take(&self) -> bool {
+        self.raised.swap(false, Ordering::SeqCst)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for SignalFlag {
+    fn drop(&mut self) {
+        signal_hook::low_level::unregister(self.signal_id);
+    }
+}
+
+// Flag raised by Ctrl+C. While it is registered Ctrl+C only raises the flag;
+// otherwise it terminates the process as usual.
+pub struct InterruptFlag {
+    flag: SignalFlag,
+}
+
+impl InterruptFlag {
+    pub fn register() -> io::Result<Self> {
+        #[cfg(unix)]
+        default_on_interrupt()?.store(false, Ordering::SeqCst);
+        Ok(InterruptFlag {
+            flag: SignalFlag::register(SIGINT)?,
+        })
+    }
+
+    // Whether Ctrl+C was pressed since the last call
+    pub fn take(&self) -> bool {
+        self.flag.take()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for InterruptFlag {
+    fn drop(&mut self) {
+        if let Ok(default) = default_on_interrupt() {
+            default.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+// Once a handler for SIGINT is installed, unregistering it doesn't bring the
+// default action back. This flag does, for as long as it is set.
+#[cfg(unix)]
+fn default_on_interrupt() -> io::Result<&'static Arc<AtomicBool>> {
+    static DEFAULT: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+    if let Some(default) = DEFAULT.get() {
+        return Ok(default);
+    }
+    let default = Arc::new(AtomicBool::new(true));
+    signal_hook::flag::register_conditional_default(SIGINT, Arc::clone(&default))?;
+    Ok(DEFAULT.get_or_init(|| default))
+}
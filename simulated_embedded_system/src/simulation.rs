@@ -0,0 +1,1195 @@
+use crate::alerts::{AlertManager, AlertStateFile};
+use crate::baseline::Baseline;
+use crate::bus::{AdcBus, Channel, ReadLatency};
+use crate::config::{
+    AlertChannel, AlertConfig, Config, ExitCodeConfig, OffTickRates, SensorColor, SensorConfig,
+    TemperatureUnit, ThresholdsWatcher,
+};
+use crate::display::{Display, Displays};
+use crate::events::{Event, EventHistory};
+use crate::filter::SensorFilters;
+use crate::history::SharedHistory;
+use crate::logger::FileLogger;
+use crate::maintenance::MaintenanceMode;
+use crate::metrics::SharedMetrics;
+#[cfg(feature = "mqtt")]
+use crate::mqtt::MqttPublisher;
+use crate::notify::Notifier;
+use crate::scenario::Scenario;
+use crate::sensor::{self, Reading};
+use crate::signals::{InterruptFlag, SignalFlag, SIGHUP};
+use crate::stats::{calculate_statistics, JitterStats, RollingWindow, RunningStats};
+use crate::stats_output::StatsOutput;
+#[cfg(feature = "statsd")]
+use crate::statsd::StatsdSink;
+use chrono::Local;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Options chosen for a single simulation run
+pub struct RunOptions {
+    pub duration_seconds: u64,
+    pub interactive: bool,
+    pub force: bool,
+    pub scenario: Option<Scenario>,
+    // `--tag key=value` metadata recorded with the run
+    pub tags: BTreeMap<String, String>,
+    // `run --replay`, taking precedence over `replay_file` under `[sensors]`
+    pub replay_file: Option<String>,
+}
+
+// Why a run ended before its duration had elapsed
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EarlyEnd {
+    // Ctrl+C
+    Interrupted,
+    // The replayed log ran out of readings
+    ReplayExhausted,
+}
+
+impl EarlyEnd {
+    fn reason(self) -> &'static str {
+        match self {
+            EarlyEnd::Interrupted => "interrupted",
+            EarlyEnd::ReplayExhausted => "replay file exhausted",
+        }
+    }
+}
+
+// Sampling schedule of one sensor. Each interval is the sampling rate plus a
+// random dither of up to ±`max_dither`, so samples aren't perfectly periodic.
+// With `accumulate`, a sample taken late on a tick shortens the next interval
+// by as much, keeping the average rate on the configured one.
+struct SampleClock {
+    rate: Duration,
+    max_dither: Duration,
+    accumulate: bool,
+    last_sample: Instant,
+    interval: Duration,
+}
+
+impl SampleClock {
+    fn new(rate: Duration, max_dither: Duration, accumulate: bool, rng: &mut StdRng) -> Self {
+        SampleClock {
+            rate,
+            max_dither,
+            accumulate,
+            last_sample: Instant::now(),
+            interval: dithered(rate, max_dither, rng),
+        }
+    }
+
+    fn set_rate(&mut self, rate: Duration) {
+        self.rate = rate;
+    }
+
+    // Check whether the sensor is due, recording how late the sample is
+    fn due(&mut self, rng: &mut StdRng, jitter: Option<&mut JitterStats>) -> bool {
+        let elapsed = self.last_sample.elapsed();
+        if elapsed < self.interval {
+            return false;
+        }
+        let late = elapsed - self.interval;
+        if let Some(jitter) = jitter {
+            jitter.record(late);
+        }
+        // A sample more than a whole interval late starts the schedule over
+        // rather than being made up with a burst of samples
+        self.last_sample = if self.accumulate && late < self.interval {
+            self.last_sample + self.interval
+        } else {
+            Instant::now()
+        };
+        self.interval = dithered(self.rate, self.max_dither, rng);
+        true
+    }
+}
+
+fn dithered(rate: Duration, max_dither: Duration, rng: &mut StdRng) -> Duration {
+    if max_dither.is_zero() {
+        return rate;
+    }
+    let max_dither = max_dither.as_secs_f64();
+    let offset = rng.gen_range(-max_dither..=max_dither);
+    Duration::from_secs_f64((rate.as_secs_f64() + offset).max(0.0))
+}
+
+// What happened during a run, used to pick the process exit code and for
+// inspecting the run through the library
+#[derive(Debug, Default)]
+pub struct RunOutcome {
+    // Identifies the run in its log, statistics output and summary
+    pub run_id: String,
+    pub alerts_fired: u64,
+    pub records: u64,
+    pub dropped_records: u64,
+    pub events: EventHistory,
+    // Cancelled before it started or interrupted with Ctrl+C
+    pub stopped_early: bool,
+}
+
+impl RunOutcome {
+    // Fraction of records that made it into the log
+    pub fn data_quality(&self) -> f64 {
+        if self.records == 0 {
+            return 1.0;
+        }
+        1.0 - self.dropped_records as f64 / self.records as f64
+    }
+
+    // Exit code under the configured policy; alerts take precedence over data quality
+    pub fn exit_code(&self, policy: &ExitCodeConfig) -> u8 {
+        if policy.alert != 0 && self.alerts_fired > 0 {
+            policy.alert
+        } else if policy.data_quality != 0 && self.data_quality() < policy.min_data_quality {
+            policy.data_quality
+        } else {
+            0
+        }
+    }
+}
+
+// Run summary printed at the end of a simulation
+pub struct RunSummary {
+    run_id: String,
+    tags: BTreeMap<String, String>,
+    duration_seconds: u64,
+    // Seconds into the run at which it ended early, and why
+    ended_early: Option<(u64, EarlyEnd)>,
+    // Wall-clock time from the start of the run to its end
+    elapsed: Duration,
+    tick_interval_ms: u64,
+    off_tick_rates: OffTickRates,
+    temperature_unit: TemperatureUnit,
+    // Rows of readings logged
+    samples: u64,
+    temperature: RunningStats,
+    humidity: RunningStats,
+    light: RunningStats,
+    alerts_fired: u64,
+    // Alerts by sensor and kind, e.g. "Temperature high"
+    alerts_by_category: BTreeMap<String, u64>,
+    alerts_suppressed_by_rule: u64,
+    dropped_log_records: u64,
+    dropped_frames: u64,
+    suppressed_alerts: u64,
+    restarts: u32,
+    peak_memory_bytes: Option<u64>,
+    temperature_jitter: JitterStats,
+    humidity_jitter: JitterStats,
+    light_jitter: JitterStats,
+    read_latency: Option<ReadLatency>,
+    read_timeouts: BTreeMap<String, u64>,
+    dropouts: BTreeMap<String, u64>,
+}
+
+impl RunSummary {
+    pub fn print_summary(&self) {
+        print!("{}", self);
+    }
+
+    // Append the summary to `path`, after any earlier runs' summaries
+    pub fn write_summary(&self, path: &str) -> io::Result<()> {
+        let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+        write!(file, "{}", self)
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "=== Run Summary ===")?;
+        writeln!(f, "Run ID: {}", self.run_id)?;
+        if !self.tags.is_empty() {
+            writeln!(f, "Tags: {}", format_tags(&self.tags))?;
+        }
+        match self.ended_early {
+            Some((elapsed, end)) => writeln!(
+                f,
+                "Duration: {}s of {}s ({})",
+                elapsed,
+                self.duration_seconds,
+                end.reason()
+            )?,
+            None => writeln!(f, "Duration: {}s", self.duration_seconds)?,
+        }
+        writeln!(f, "Elapsed time: {:.1}s", self.elapsed.as_secs_f64())?;
+        writeln!(
+            f,
+            "Sampling tick: {} ms (off-tick rates: {})",
+            self.tick_interval_ms, self.off_tick_rates
+        )?;
+        writeln!(f, "Samples collected: {}", self.samples)?;
+        writeln!(f, "Readings over the run (min / max / average):")?;
+        for (name, unit, stats) in [
+            (
+                "Temperature",
+                self.temperature_unit.symbol(),
+                &self.temperature,
+            ),
+            ("Humidity", "%", &self.humidity),
+            ("Light", "%", &self.light),
+        ] {
+            match stats.summary() {
+                Some((average, min, max)) => writeln!(
+                    f,
+                    "  {}: {:.2}{unit} / {:.2}{unit} / {:.2}{unit}",
+                    name,
+                    min,
+                    max,
+                    average,
+                    unit = unit
+                )?,
+                None => writeln!(f, "  {}: no valid readings", name)?,
+            }
+        }
+        writeln!(f, "Alerts fired: {}", self.alerts_fired)?;
+        for (category, count) in &self.alerts_by_category {
+            writeln!(f, "  {}: {}", category, count)?;
+        }
+        if self.alerts_suppressed_by_rule > 0 {
+            writeln!(
+                f,
+                "  Not shown or sent (suppressed): {}",
+                self.alerts_suppressed_by_rule
+            )?;
+        }
+        match self.peak_memory_bytes {
+            Some(bytes) => writeln!(
+                f,
+                "Peak memory (RSS): {:.1} MiB",
+                bytes as f64 / (1024.0 * 1024.0)
+            )?,
+            None => writeln!(f, "Peak memory (RSS): unavailable")?,
+        }
+        if self.dropped_log_records > 0 {
+            writeln!(
+                f,
+                "Data loss: {} record(s) could not be written to the log",
+                self.dropped_log_records
+            )?;
+        }
+        if self.dropped_frames > 0 {
+            writeln!(
+                f,
+                "Display frames skipped to keep up: {}",
+                self.dropped_frames
+            )?;
+        }
+        if self.suppressed_alerts > 0 {
+            writeln!(
+                f,
+                "Alerts suppressed during maintenance: {}",
+                self.suppressed_alerts
+            )?;
+        }
+        if self.restarts > 0 {
+            writeln!(f, "Restarts after a panic: {}", self.restarts)?;
+        }
+        if !self.read_timeouts.is_empty() {
+            let timeouts: Vec<String> = self
+                .read_timeouts
+                .iter()
+                .map(|(name, count)| format!("{}: {}", name, count))
+                .collect();
+            writeln!(f, "Read timeouts: {}", timeouts.join(", "))?;
+        }
+        if !self.dropouts.is_empty() {
+            let dropouts: Vec<String> = self
+                .dropouts
+                .iter()
+                .map(|(name, count)| format!("{}: {}", name, count))
+                .collect();
+            writeln!(f, "Read dropouts: {}", dropouts.join(", "))?;
+        }
+        write_timings(
+            f,
+            "Sampling jitter",
+            [
+                ("Temperature", &self.temperature_jitter),
+                ("Humidity", &self.humidity_jitter),
+                ("Light", &self.light_jitter),
+            ],
+        )?;
+        if let Some(latency) = &self.read_latency {
+            write_timings(
+                f,
+                "ADC read latency",
+                [
+                    ("Temperature", &latency.temperature),
+                    ("Humidity", &latency.humidity),
+                    ("Light", &latency.light),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Tags as `key=value` pairs, e.g. for the run summary
+pub fn format_tags(tags: &BTreeMap<String, String>) -> String {
+    tags.iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Mean / p95 / max of a timing per sensor
+fn write_timings(
+    f: &mut fmt::Formatter,
+    title: &str,
+    rows: [(&str, &JitterStats); 3],
+) -> fmt::Result {
+    writeln!(f, "{} (mean / p95 / max):", title)?;
+    for (name, timing) in rows {
+        match timing.summary() {
+            Some((mean, p95, max)) => writeln!(
+                f,
+                "  {}: {:.2} ms / {:.2} ms / {:.2} ms",
+                name, mean, p95, max
+            )?,
+            None => writeln!(f, "  {}: no samples", name)?,
+        }
+    }
+    Ok(())
+}
+
+// Identifier of a new run: its start time plus a random suffix, so runs
+// started in the same second still differ, e.g. `20240501-150000-3fa2`
+fn new_run_id() -> String {
+    format!(
+        "{}-{:04x}",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        rand::random::<u16>()
+    )
+}
+
+// Peak resident set size of the process in bytes. The OS keeps the
+// high-water mark itself, so querying once at the end of a run is enough.
+#[cfg(unix)]
+fn peak_rss_bytes() -> Option<u64> {
+    // SAFETY: getrusage only writes into the zero-initialised struct we pass
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return None;
+    }
+    let max_rss = u64::try_from(usage.ru_maxrss).ok()?;
+    // macOS reports bytes, other Unixes kilobytes
+    if cfg!(target_os = "macos") {
+        Some(max_rss)
+    } else {
+        Some(max_rss * 1024)
+    }
+}
+
+#[cfg(not(unix))]
+fn peak_rss_bytes() -> Option<u64> {
+    None
+}
+
+// Statistics gathered over a run, kept across supervised restarts
+struct RunStats {
+    alerts_fired: u64,
+    alerts_by_category: BTreeMap<String, u64>,
+    // Alerts counted but kept off every channel by `suppress`
+    alerts_suppressed_by_rule: u64,
+    events: EventHistory,
+    dropped_frames: u64,
+    samples: u64,
+    // Whole-run statistics, unlike the windows of the last few values
+    temperature: RunningStats,
+    humidity: RunningStats,
+    light: RunningStats,
+    temperature_window: RollingWindow,
+    humidity_window: RollingWindow,
+    light_window: RollingWindow,
+    temperature_jitter: JitterStats,
+    humidity_jitter: JitterStats,
+    light_jitter: JitterStats,
+    read_latency: ReadLatency,
+    // Sensor reads abandoned after their source's timeout, by sensor name
+    read_timeouts: BTreeMap<String, u64>,
+    // Reads that dropped out under `failure_rate`, by sensor name
+    dropouts: BTreeMap<String, u64>,
+    stats_output: Option<StatsOutput>,
+    notifier: Notifier,
+    metrics: Option<SharedMetrics>,
+    #[cfg(feature = "statsd")]
+    statsd: Option<StatsdSink>,
+    #[cfg(feature = "mqtt")]
+    mqtt: Option<MqttPublisher>,
+}
+
+impl RunStats {
+    fn new(
+        config: &Config,
+        run_id: &str,
+        _tags: &BTreeMap<String, String>,
+        metrics: Option<&SharedMetrics>,
+    ) -> Self {
+        let capacity = config.stats.reservoir_size;
+        // Seeded runs sample their statistics reproducibly too
+        let seed = config.sensors.seed;
+        RunStats {
+            alerts_fired: 0,
+            alerts_by_category: BTreeMap::new(),
+            alerts_suppressed_by_rule: 0,
+            events: EventHistory::default(),
+            dropped_frames: 0,
+            samples: 0,
+            temperature: RunningStats::default(),
+            humidity: RunningStats::default(),
+            light: RunningStats::default(),
+            temperature_window: RollingWindow::new(
+                config.display.window_size(&config.display.temperature),
+            ),
+            humidity_window: RollingWindow::new(
+                config.display.window_size(&config.display.humidity),
+            ),
+            light_window: RollingWindow::new(config.display.window_size(&config.display.light)),
+            temperature_jitter: JitterStats::with_reservoir(capacity, seed),
+            humidity_jitter: JitterStats::with_reservoir(capacity, seed),
+            light_jitter: JitterStats::with_reservoir(capacity, seed),
+            read_latency: ReadLatency::with_reservoir(capacity, seed),
+            read_timeouts: BTreeMap::new(),
+            dropouts: BTreeMap::new(),
+            stats_output: config
+                .stats_output
+                .as_ref()
+                .map(|output| StatsOutput::new(output, config.storage.nan_representation, run_id)),
+            notifier: Notifier::new(&config.notifications, run_id),
+            metrics: metrics.cloned(),
+            #[cfg(feature = "statsd")]
+            statsd: config.statsd.as_ref().and_then(|statsd| {
+                StatsdSink::connect(statsd, run_id, _tags)
+                    .map_err(|e| {
+                        println!(
+                            "[WARNING]: Not sending metrics to StatsD at {}:{}: {}",
+                            statsd.host, statsd.port, e
+                        )
+                    })
+                    .ok()
+            }),
+            #[cfg(feature = "mqtt")]
+            mqtt: config
+                .mqtt
+                .as_ref()
+                .map(|mqtt| MqttPublisher::connect(mqtt, run_id, config.display.temperature_unit)),
+        }
+    }
+
+    // Sampling jitter of the sensor on `channel`; only the core sensors are tracked
+    fn jitter(&mut self, channel: Channel) -> Option<&mut JitterStats> {
+        match channel {
+            Channel::Temperature => Some(&mut self.temperature_jitter),
+            Channel::Humidity => Some(&mut self.humidity_jitter),
+            Channel::Light => Some(&mut self.light_jitter),
+            Channel::Pressure | Channel::Co2 => None,
+        }
+    }
+}
+
+// Run the simulation, restarting it after a panic up to `supervisor.max_restarts` times
+pub fn run_simulation(
+    config: &Config,
+    history: Option<&SharedHistory>,
+    metrics: Option<&SharedMetrics>,
+    options: &RunOptions,
+) -> Result<RunOutcome, Box<dyn Error>> {
+    let duration_seconds = options.duration_seconds;
+    let run_id = new_run_id();
+    startup_gate(&config.sensors)?;
+    let mut logger = match FileLogger::create(config, options)? {
+        Some(logger) => logger,
+        None => {
+            println!("Simulation cancelled.");
+            return Ok(RunOutcome {
+                stopped_early: true,
+                ..RunOutcome::default()
+            });
+        }
+    };
+    let mut maintenance = MaintenanceMode::new()?;
+    logger.log_run_start(&run_id, &options.tags)?;
+    let mut stats = RunStats::new(config, &run_id, &options.tags, metrics);
+    let mut restarts = 0;
+
+    report_off_tick_rates(&config.sensors);
+    println!(
+        "Starting simulation {} for {} seconds...",
+        run_id, duration_seconds
+    );
+    let start_time = Instant::now();
+
+    let early_end = loop {
+        // The run only borrows state that stays consistent between ticks, so
+        // resuming with it after a panic is safe
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            run_loop(
+                config,
+                history,
+                options,
+                start_time,
+                &mut logger,
+                &mut maintenance,
+                &mut stats,
+            )
+        }));
+        match result {
+            Ok(result) => break result?,
+            Err(_) if restarts < config.supervisor.max_restarts => {
+                restarts += 1;
+                println!(
+                    "[WARNING]: Simulation panicked, restarting ({}/{})",
+                    restarts, config.supervisor.max_restarts
+                );
+                stats.events.record(Event::Restarted);
+                if config.supervisor.reset_stats {
+                    if let Some(output) = stats.stats_output.take() {
+                        output.finish()?;
+                    }
+                    // Alerts and events still belong to the run's outcome, and
+                    // pending notifications are still sent
+                    stats = RunStats {
+                        alerts_fired: stats.alerts_fired,
+                        alerts_by_category: mem::take(&mut stats.alerts_by_category),
+                        alerts_suppressed_by_rule: stats.alerts_suppressed_by_rule,
+                        events: mem::take(&mut stats.events),
+                        notifier: stats.notifier,
+                        ..RunStats::new(config, &run_id, &options.tags, metrics)
+                    };
+                }
+            }
+            Err(_) => {
+                return Err(format!(
+                    "Simulation panicked after {} restart(s), giving up",
+                    restarts
+                )
+                .into())
+            }
+        }
+    };
+
+    stats.notifier.flush();
+    if let Some(output) = stats.stats_output.take() {
+        output.finish()?;
+    }
+
+    let outcome = RunOutcome {
+        run_id,
+        alerts_fired: stats.alerts_fired,
+        records: logger.records,
+        dropped_records: logger.dropped_records,
+        events: stats.events,
+        stopped_early: early_end == Some(EarlyEnd::Interrupted),
+    };
+    logger.finish()?;
+
+    let summary = RunSummary {
+        run_id: outcome.run_id.clone(),
+        tags: options.tags.clone(),
+        duration_seconds,
+        ended_early: early_end.map(|end| (start_time.elapsed().as_secs(), end)),
+        elapsed: start_time.elapsed(),
+        tick_interval_ms: config.sensors.tick_interval_ms,
+        temperature_unit: config.display.temperature_unit,
+        off_tick_rates: config.sensors.off_tick_rates,
+        samples: stats.samples,
+        temperature: stats.temperature,
+        humidity: stats.humidity,
+        light: stats.light,
+        alerts_fired: outcome.alerts_fired,
+        alerts_by_category: stats.alerts_by_category,
+        alerts_suppressed_by_rule: stats.alerts_suppressed_by_rule,
+        dropped_log_records: outcome.dropped_records,
+        dropped_frames: stats.dropped_frames,
+        suppressed_alerts: maintenance.suppressed_alerts,
+        restarts,
+        peak_memory_bytes: peak_rss_bytes(),
+        temperature_jitter: stats.temperature_jitter,
+        humidity_jitter: stats.humidity_jitter,
+        light_jitter: stats.light_jitter,
+        read_latency: config.sensors.bus.as_ref().map(|_| stats.read_latency),
+        read_timeouts: stats.read_timeouts,
+        dropouts: stats.dropouts,
+    };
+    summary.print_summary();
+    if let Some(path) = &config.run.summary_file {
+        if let Err(e) = summary.write_summary(path) {
+            println!(
+                "[WARNING]: Could not write the run summary to {}: {}",
+                path, e
+            );
+        }
+    }
+    Ok(outcome)
+}
+
+// Point out sampling rates the tick can't hit exactly, and how they are scheduled
+fn report_off_tick_rates(config: &SensorConfig) {
+    for channel in config.off_tick_channels() {
+        let Some(rate) = config.sampling_rate(channel) else {
+            continue;
+        };
+        if rate < Duration::from_millis(config.tick_interval_ms) {
+            println!(
+                "[WARNING]: The {:?} sampling rate of {}s is shorter than the {} ms tick; it is sampled once every tick",
+                channel,
+                rate.as_secs_f64(),
+                config.tick_interval_ms
+            );
+            continue;
+        }
+        match config.off_tick_rates {
+            OffTickRates::Warn => println!(
+                "[WARNING]: The {:?} sampling rate of {}s is not a multiple of the {} ms tick; it is sampled on the next tick after each due time",
+                channel,
+                rate.as_secs_f64(),
+                config.tick_interval_ms
+            ),
+            OffTickRates::Accumulate => println!(
+                "[Startup]: The {:?} sampling rate of {}s is not a multiple of the {} ms tick; tick lateness is accumulated to keep its average rate",
+                channel,
+                rate.as_secs_f64(),
+                config.tick_interval_ms
+            ),
+        }
+    }
+}
+
+// Wait for each gated sensor to return a valid reading, failing with the
+// first sensor that doesn't within its attempts and timeout
+fn startup_gate(config: &SensorConfig) -> Result<(), Box<dyn Error>> {
+    let gate = |channel| match channel {
+        Channel::Temperature => config.temperature_gate.as_ref(),
+        Channel::Humidity => config.humidity_gate.as_ref(),
+        Channel::Light => config.light_gate.as_ref(),
+        Channel::Pressure | Channel::Co2 => None,
+    };
+    for mut sensor in sensor::from_config(config) {
+        let channel = sensor.channel();
+        let Some(gate) = gate(channel) else { continue };
+        println!("[Startup]: Waiting for the {:?} sensor...", channel);
+        let start = Instant::now();
+        let timeout = Duration::from_millis(gate.timeout_ms);
+        let mut last = None;
+        for attempt in 1..=gate.attempts {
+            let value = sensor.read();
+            if gate.accepts(value) {
+                println!(
+                    "[Startup]: {:?} sensor ready after {} attempt(s) ({:.2})",
+                    channel, attempt, value
+                );
+                last = None;
+                break;
+            }
+            last = Some((attempt, value));
+            if attempt == gate.attempts || start.elapsed() >= timeout {
+                break;
+            }
+            let remaining = timeout.saturating_sub(start.elapsed());
+            thread::sleep(Duration::from_millis(gate.interval_ms).min(remaining));
+        }
+        if let Some((attempts, value)) = last {
+            return Err(format!(
+                "{:?} sensor failed the startup gate: no valid reading after {} attempt(s) in {:.1}s (last reading {:.2})",
+                channel,
+                attempts,
+                start.elapsed().as_secs_f64(),
+                value
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+// Sample, display, log and check alerts until the run's duration has elapsed,
+// Ctrl+C is pressed or the replayed log runs out, returning why it ended early
+fn run_loop(
+    config: &Config,
+    history: Option<&SharedHistory>,
+    options: &RunOptions,
+    start_time: Instant,
+    logger: &mut FileLogger,
+    maintenance: &mut MaintenanceMode,
+    stats: &mut RunStats,
+) -> Result<Option<EarlyEnd>, Box<dyn Error>> {
+    let duration_seconds = options.duration_seconds;
+    let replay_file = options
+        .replay_file
+        .as_deref()
+        .or(config.sensors.replay_file.as_deref());
+    let mut sensors = match replay_file {
+        Some(path) => sensor::replay_from_log(
+            &config.sensors,
+            path,
+            config.storage.nan_representation,
+            config.display.temperature_unit,
+        )?,
+        None => sensor::from_config(&config.sensors),
+    };
+    let mut scenario_phase = None;
+    let mut bus = config.sensors.bus.as_ref().map(AdcBus::new);
+    let mut filters = SensorFilters::new(&config.sensors);
+    let mut display = Displays::from_config(&config.display)?;
+    let baseline = config
+        .display
+        .baseline_path
+        .as_deref()
+        .map(Baseline::load)
+        .transpose()?;
+    // Alert conditions carry on from before a restart when their state is kept
+    let alert_state_path = config.supervisor.alert_state_file.as_deref();
+    let mut alert_state = alert_state_path.map(AlertStateFile::new);
+    let unit = config.display.temperature_unit;
+    let mut alert_manager = match alert_state.as_mut() {
+        Some(state) => state.load(unit, Instant::now()).unwrap_or_else(|e| {
+            println!(
+                "[WARNING]: Ignoring alert state file {}: {}",
+                alert_state_path.unwrap_or_default(),
+                e
+            );
+            AlertManager::new(unit)
+        }),
+        None => AlertManager::new(unit),
+    };
+    let mut alert_config = config.alerts.clone();
+    let mut thresholds_watcher = config
+        .alerts
+        .thresholds_file
+        .as_deref()
+        .map(|path| ThresholdsWatcher::new(path, unit));
+    let reload = SignalFlag::register(SIGHUP)?;
+    let interrupt = InterruptFlag::register()?;
+    let mut last_stats = None;
+
+    // Seeded runs dither their sampling times reproducibly
+    let mut rng = match config.sensors.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let max_dither = Duration::from_millis(config.sensors.sampling_dither_ms);
+    let accumulate = config.sensors.off_tick_rates == OffTickRates::Accumulate;
+    let mut clocks: HashMap<String, SampleClock> = sensors
+        .iter()
+        .map(|sensor| {
+            let clock = SampleClock::new(sensor.sampling_rate(), max_dither, accumulate, &mut rng);
+            (sensor.name().to_string(), clock)
+        })
+        .collect();
+    // Latest readings of the optional sensors, NaN until their first sample
+    let configured = |channel| sensors.iter().any(|sensor| sensor.channel() == channel);
+    let mut pressure = configured(Channel::Pressure).then_some(f32::NAN);
+    let mut co2 = configured(Channel::Co2).then_some(f32::NAN);
+    // Latest raw and filtered readings of the core sensors, likewise NaN
+    // until their first sample
+    let mut raw = Reading {
+        temperature: f32::NAN,
+        humidity: f32::NAN,
+        light: f32::NAN,
+        pressure: None,
+        co2: None,
+    };
+    let mut latest = raw;
+    let failure_rate = config.sensors.failure_rate;
+
+    let early_end = loop {
+        // Check if the duration has elapsed
+        if start_time.elapsed() >= Duration::from_secs(duration_seconds) {
+            println!("Simulation completed after {} seconds.", duration_seconds);
+            break None;
+        }
+
+        // Ctrl+C ends the run early; the log and summary are finished as usual
+        if interrupt.take() {
+            println!(
+                "Simulation interrupted after {} seconds.",
+                start_time.elapsed().as_secs()
+            );
+            break Some(EarlyEnd::Interrupted);
+        }
+
+        // A replay ends with the first sensor to use up its recorded values
+        if sensors.iter().any(|sensor| sensor.exhausted()) {
+            println!(
+                "Replay file exhausted after {} seconds.",
+                start_time.elapsed().as_secs()
+            );
+            break Some(EarlyEnd::ReplayExhausted);
+        }
+
+        // Pick up edited thresholds, keeping the previous ones if the file is invalid
+        if let Some(watcher) = thresholds_watcher.as_mut() {
+            match watcher.poll() {
+                Some(Ok(thresholds)) => {
+                    alert_config = thresholds;
+                    stats.events.record(Event::ThresholdsReloaded);
+                    println!("[Config]: Loaded alert thresholds from {}", watcher.path);
+                }
+                Some(Err(e)) => println!(
+                    "[WARNING]: Ignoring invalid thresholds file {}: {}",
+                    watcher.path, e
+                ),
+                None => {}
+            }
+        }
+
+        // SIGHUP re-reads the config; thresholds and sampling rates apply right
+        // away. Thresholds from `thresholds_file` still take precedence.
+        if reload.take() {
+            let reloaded = config.reload().and_then(|reloaded| {
+                let alerts = match reloaded.alerts.thresholds_file.as_deref() {
+                    Some(path) => AlertConfig::load(path, unit)?,
+                    None => reloaded.alerts.clone(),
+                };
+                Ok((reloaded, alerts))
+            });
+            match reloaded {
+                Ok((reloaded, alerts)) => {
+                    for sensor in &sensors {
+                        let rate = reloaded.sensors.sampling_rate(sensor.channel());
+                        if let (Some(clock), Some(rate)) = (clocks.get_mut(sensor.name()), rate) {
+                            clock.set_rate(rate);
+                        }
+                    }
+                    alert_config = alerts;
+                    stats.events.record(Event::ConfigReloaded);
+                    println!("[Config]: Reloaded {}", config.source);
+                }
+                Err(e) => println!(
+                    "[WARNING]: Ignoring invalid config {}: {}",
+                    config.source, e
+                ),
+            }
+        }
+
+        match maintenance.poll() {
+            Some(true) => {
+                println!("[Maintenance]: Entered maintenance mode, alerts suppressed");
+                stats.events.record(Event::MaintenanceStarted);
+            }
+            Some(false) => {
+                println!("[Maintenance]: Left maintenance mode, alerts resumed");
+                stats.events.record(Event::MaintenanceEnded);
+            }
+            None => {}
+        }
+
+        // Follow the scenario's script
+        if let Some(scenario) = &options.scenario {
+            let (index, phase) = scenario.phase_at(start_time.elapsed());
+            if scenario_phase != Some(index) {
+                scenario_phase = Some(index);
+                for sensor in &mut sensors {
+                    sensor.set_phase(phase);
+                }
+                println!("[Scenario]: Phase {}/{}", index + 1, scenario.phases.len());
+            }
+        }
+
+        // Read each sensor that is due, unless the read drops out
+        let mut values = HashMap::new();
+        let mut dropouts = Vec::new();
+        for sensor in &mut sensors {
+            let channel = sensor.channel();
+            let Some(clock) = clocks.get_mut(sensor.name()) else {
+                continue;
+            };
+            if clock.due(&mut rng, stats.jitter(channel)) {
+                if failure_rate > 0.0 && rng.gen_bool(failure_rate) {
+                    dropouts.push((channel, sensor.name().to_string()));
+                    continue;
+                }
+                match bus.as_mut() {
+                    Some(bus) => bus.request(channel),
+                    None => {
+                        values.insert(channel, sensor.read());
+                    }
+                }
+            }
+        }
+
+        // Wait for the shared ADC to convert the requested channels
+        if let Some(bus) = bus.as_mut() {
+            values.extend(bus.convert(&mut sensors, &mut stats.read_latency));
+        }
+        for sensor in &mut sensors {
+            let timeouts = sensor.take_timeouts();
+            if timeouts > 0 {
+                *stats
+                    .read_timeouts
+                    .entry(sensor.name().to_string())
+                    .or_default() += timeouts;
+            }
+        }
+        // A dropped-out read counts as a failed reading, logged as NaN
+        for (channel, name) in dropouts {
+            println!("[WARNING]: {} read failed", name);
+            logger.log_dropout(&name)?;
+            *stats.dropouts.entry(name).or_default() += 1;
+            values.insert(channel, f32::NAN);
+        }
+        if let Some(&value) = values.get(&Channel::Pressure) {
+            pressure = Some(value);
+        }
+        if let Some(&value) = values.get(&Channel::Co2) {
+            co2 = Some(value);
+        }
+
+        // Smooth the new readings with the configured filters. Sensors that
+        // weren't due keep their latest reading. Temperatures are filtered in
+        // Celsius and converted to the display unit from here on.
+        let raw_temp = values
+            .get(&Channel::Temperature)
+            .map(|&celsius| (celsius, unit.from_celsius(celsius)));
+        let raw_hum = values.get(&Channel::Humidity).copied();
+        let raw_lgt = values.get(&Channel::Light).copied();
+        if let Some((celsius, value)) = raw_temp {
+            raw.temperature = value;
+            latest.temperature = unit.from_celsius(filters.temperature(celsius));
+            stats.temperature.push(latest.temperature);
+            stats.temperature_window.push(latest.temperature);
+        }
+        if let Some(value) = raw_hum {
+            raw.humidity = value;
+            latest.humidity = filters.humidity(value);
+            stats.humidity.push(latest.humidity);
+            stats.humidity_window.push(latest.humidity);
+        }
+        if let Some(value) = raw_lgt {
+            raw.light = value;
+            latest.light = filters.light(value);
+            stats.light.push(latest.light);
+            stats.light_window.push(latest.light);
+        }
+
+        // Display sensor data whenever one of the core sensors was read
+        if raw_temp.is_some() || raw_hum.is_some() || raw_lgt.is_some() {
+            let raw = Reading {
+                pressure,
+                co2,
+                ..raw
+            };
+            let Reading {
+                temperature: temp,
+                humidity: hum,
+                light: lgt,
+                ..
+            } = latest;
+            let render = display.begin_frame();
+            if render {
+                let parts: Vec<(String, Option<SensorColor>)> = sensors
+                    .iter()
+                    .map(|sensor| {
+                        let value = match sensor.channel() {
+                            Channel::Temperature => temp,
+                            Channel::Humidity => hum,
+                            Channel::Light => lgt,
+                            Channel::Pressure => pressure.unwrap_or(f32::NAN),
+                            Channel::Co2 => co2.unwrap_or(f32::NAN),
+                        };
+                        // Symbols like °C and % attach to the value, unit names don't
+                        let unit = match sensor.channel() {
+                            Channel::Temperature => unit.symbol(),
+                            _ => sensor.unit(),
+                        };
+                        let separator = if unit.starts_with(char::is_alphabetic) {
+                            " "
+                        } else {
+                            ""
+                        };
+                        let deviation = baseline
+                            .as_ref()
+                            .and_then(|baseline| baseline.describe(sensor.channel(), value))
+                            .map(|deviation| format!(" ({})", deviation))
+                            .unwrap_or_default();
+                        // Sensors that weren't read on this tick show their
+                        // latest value, marked as stale
+                        let stale = if values.contains_key(&sensor.channel()) {
+                            ""
+                        } else {
+                            " (stale)"
+                        };
+                        let color = config
+                            .display
+                            .sensor(sensor.channel())
+                            .and_then(|label| label.color);
+                        (
+                            format!(
+                                "{}: {:.2}{}{}{}{}",
+                                sensor.name(),
+                                value,
+                                separator,
+                                unit,
+                                deviation,
+                                stale
+                            ),
+                            color,
+                        )
+                    })
+                    .collect();
+                display.show_readings(&parts);
+            }
+
+            // Log sensor data to a file
+            let reading = Reading {
+                temperature: temp,
+                humidity: hum,
+                light: lgt,
+                pressure,
+                co2,
+            };
+            logger.log_reading(&reading, &raw, maintenance.active)?;
+            stats.events.record(Event::Reading(reading));
+            if let Some(output) = stats.stats_output.as_mut() {
+                output.record(&reading)?;
+            }
+            #[cfg(feature = "statsd")]
+            if let Some(statsd) = &stats.statsd {
+                statsd.send_reading(&reading);
+            }
+            #[cfg(feature = "mqtt")]
+            if let Some(mqtt) = &stats.mqtt {
+                let read: Vec<Channel> = values.keys().copied().collect();
+                mqtt.publish_reading(&reading, &read);
+            }
+
+            // Share the reading with the HTTP server
+            if let Some(history) = history {
+                history
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push(reading);
+            }
+            if let Some(metrics) = &stats.metrics {
+                metrics
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .record_reading(&reading);
+            }
+
+            stats.samples += 1;
+
+            // Display the real-time graph
+            if render && config.display.real_time_graph {
+                display.show_graph(
+                    "Temperature",
+                    stats.temperature_window.values(),
+                    stats.temperature_window.size(),
+                    &config.display.temperature,
+                );
+            }
+
+            // Check for alerts
+            let alerts = alert_manager.check(&reading, &alert_config, Instant::now());
+            if let Some(state) = alert_state.as_mut() {
+                if let Err(e) = state.save(&alert_manager) {
+                    println!(
+                        "[WARNING]: Could not save alert state to {}: {}",
+                        alert_state_path.unwrap_or_default(),
+                        e
+                    );
+                }
+            }
+            if maintenance.active {
+                maintenance.suppressed_alerts += alerts.len() as u64;
+            } else {
+                stats.alerts_fired += alerts.len() as u64;
+                if let Some(metrics) = &stats.metrics {
+                    metrics
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .count_alerts(alerts.len() as u64);
+                }
+                for alert in &alerts {
+                    logger.log_to_alert_log(alert)?;
+                    *stats
+                        .alerts_by_category
+                        .entry(format!("{:?} {}", alert.sensor, alert.kind))
+                        .or_default() += 1;
+                    #[cfg(feature = "statsd")]
+                    if let Some(statsd) = &stats.statsd {
+                        statsd.count_alert(alert);
+                    }
+                    if alert_config.suppresses(alert.sensor, alert.kind) {
+                        stats.alerts_suppressed_by_rule += 1;
+                        continue;
+                    }
+                    let channels = alert_config.channels(alert.sensor);
+                    if channels.contains(&AlertChannel::Console) {
+                        display.show_alert(&alert.message);
+                    }
+                    if channels.contains(&AlertChannel::File) {
+                        logger.log_alert(&alert.message)?;
+                    }
+                    // Don't page again for a condition paged before a restart
+                    if !alert_manager.fired_before_restart(alert) {
+                        stats.notifier.notify(&alert.message, channels);
+                    }
+                }
+            }
+            for alert in alerts {
+                stats.events.record(Event::Alert {
+                    suppressed: maintenance.active
+                        || alert_config.suppresses(alert.sensor, alert.kind),
+                    message: alert.message,
+                });
+            }
+
+            // Calculate and display statistics over each sensor's window, at
+            // most once every `stats_display_every_secs`
+            let stats_due = last_stats.is_none_or(|last: Instant| {
+                last.elapsed() >= Duration::from_secs(config.display.stats_display_every_secs)
+            });
+            if render && stats_due {
+                last_stats = Some(Instant::now());
+                for (name, unit, window, label) in [
+                    (
+                        "Temperature",
+                        unit.symbol(),
+                        &stats.temperature_window,
+                        &config.display.temperature,
+                    ),
+                    (
+                        "Humidity",
+                        "%",
+                        &stats.humidity_window,
+                        &config.display.humidity,
+                    ),
+                    ("Light", "%", &stats.light_window, &config.display.light),
+                ] {
+                    let Some(window_stats) = calculate_statistics(window.values()) else {
+                        continue;
+                    };
+                    display.show_statistics(&format!(
+                        "{} Average: {:.2}{unit}, Median: {:.2}{unit}, Std Dev: {:.2}{unit}, Min: {:.2}{unit}, Max: {:.2}{unit}",
+                        name,
+                        window_stats.average,
+                        window_stats.median,
+                        window_stats.std_dev,
+                        window_stats.min,
+                        window_stats.max
+                    ), label);
+                }
+            }
+            if render {
+                display.end_frame()?;
+            }
+        }
+        stats.notifier.poll();
+
+        // Wait for the next tick
+        thread::sleep(Duration::from_millis(config.sensors.tick_interval_ms));
+    };
+    stats.dropped_frames += display.dropped_frames;
+    Ok(early_end)
+}
@@ -0,0 +1,291 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// Summary statistics of a set of readings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Statistics {
+    pub average: f32,
+    pub min: f32,
+    pub max: f32,
+    pub median: f32,
+    // Population standard deviation
+    pub std_dev: f32,
+}
+
+// Statistics utility; `None` for an empty slice. Failed (NaN) readings are
+// skipped, and so is a slice of nothing else. Sums are taken in `f64`, so
+// long intervals of `[stats_output]` don't lose precision.
+pub fn calculate_statistics(values: &[f32]) -> Option<Statistics> {
+    let values: Vec<f32> = values.iter().copied().filter(|v| v.is_finite()).collect();
+    if values.is_empty() {
+        return None;
+    }
+    let count = values.len() as f64;
+    let average = values.iter().map(|&v| f64::from(v)).sum::<f64>() / count;
+    let min = values.iter().cloned().fold(f32::INFINITY, f32::min);
+    let max = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let variance = values
+        .iter()
+        .map(|&v| (f64::from(v) - average).powi(2))
+        .sum::<f64>()
+        / count;
+
+    let mut sorted = values;
+    sorted.sort_by(f32::total_cmp);
+    let middle = sorted.len() / 2;
+    let median = if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    };
+
+    Some(Statistics {
+        average: average as f32,
+        min,
+        max,
+        median,
+        std_dev: variance.sqrt() as f32,
+    })
+}
+
+// Percentile (0-100) of an ascending sorted slice, linearly interpolated
+pub fn percentile(sorted: &[f32], p: f32) -> Option<f32> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p / 100.0).clamp(0.0, 1.0) * (sorted.len() - 1) as f32;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let fraction = rank - lower as f32;
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * fraction)
+}
+
+// The most recent values of one sensor
+pub struct RollingWindow {
+    size: usize,
+    values: VecDeque<f32>,
+}
+
+impl RollingWindow {
+    pub fn new(size: usize) -> Self {
+        RollingWindow {
+            size: size.max(1),
+            values: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, value: f32) {
+        if self.values.len() == self.size {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+        // Keep the values in one slice for `values`
+        self.values.make_contiguous();
+    }
+
+    // Values the window holds once full
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // Values from oldest to newest
+    pub fn values(&self) -> &[f32] {
+        self.values.as_slices().0
+    }
+}
+
+// Samples kept for percentile estimates: all of them, or with a capacity a
+// fixed-size uniform random sample of everything seen (reservoir sampling,
+// Algorithm R), which bounds memory at the cost of some percentile accuracy.
+// With a `seed` the same values give the same sample.
+pub struct Reservoir {
+    capacity: Option<usize>,
+    seen: u64,
+    samples: Vec<f32>,
+    rng: StdRng,
+}
+
+impl Reservoir {
+    pub fn new(capacity: Option<usize>, seed: Option<u64>) -> Self {
+        Reservoir {
+            capacity,
+            seen: 0,
+            samples: Vec::new(),
+            rng: match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+        }
+    }
+
+    pub fn push(&mut self, value: f32) {
+        self.seen += 1;
+        match self.capacity {
+            Some(capacity) if self.samples.len() >= capacity => {
+                // Keep the new value with probability capacity / seen
+                let slot = self.rng.gen_range(0..self.seen);
+                if let Some(sample) = self.samples.get_mut(slot as usize) {
+                    *sample = value;
+                }
+            }
+            _ => self.samples.push(value),
+        }
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+}
+
+impl Default for Reservoir {
+    fn default() -> Self {
+        Reservoir::new(None, None)
+    }
+}
+
+// Deviations between intended and actual sample times of one sensor.
+// Mean and maximum are exact; the 95th percentile comes from the reservoir.
+#[derive(Default)]
+pub struct JitterStats {
+    deviations_ms: Reservoir,
+    count: u64,
+    mean_ms: f64,
+    max_ms: f32,
+}
+
+impl JitterStats {
+    pub fn with_reservoir(capacity: Option<usize>, seed: Option<u64>) -> Self {
+        JitterStats {
+            deviations_ms: Reservoir::new(capacity, seed),
+            ..JitterStats::default()
+        }
+    }
+
+    pub fn record(&mut self, deviation: Duration) {
+        let deviation_ms = deviation.as_secs_f32() * 1000.0;
+        self.deviations_ms.push(deviation_ms);
+        self.count += 1;
+        self.mean_ms = running_mean(self.mean_ms, self.count, deviation_ms);
+        self.max_ms = self.max_ms.max(deviation_ms);
+    }
+
+    // Mean, 95th percentile, and maximum deviation in milliseconds
+    pub fn summary(&self) -> Option<(f32, f32, f32)> {
+        let mut sorted = self.deviations_ms.samples().to_vec();
+        sorted.sort_by(f32::total_cmp);
+        let p95 = percentile(&sorted, 95.0)?;
+        Some((self.mean_ms as f32, p95, self.max_ms))
+    }
+}
+
+// Mean of `count` values, the last of which is `value`, from the mean of the
+// ones before it. Unlike a running sum, this doesn't grow with the number of
+// values, so the mean stays accurate over multi-day runs.
+fn running_mean(mean: f64, count: u64, value: f32) -> f64 {
+    mean + (f64::from(value) - mean) / count as f64
+}
+
+// Mean, minimum and maximum of all values of one sensor over a run, without
+// keeping the values. NaN readings are left out.
+#[derive(Default)]
+pub struct RunningStats {
+    count: u64,
+    mean: f64,
+    min: f32,
+    max: f32,
+}
+
+impl RunningStats {
+    pub fn push(&mut self, value: f32) {
+        if !value.is_finite() {
+            return;
+        }
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        }
+        self.count += 1;
+        self.mean = running_mean(self.mean, self.count, value);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    // Mean, minimum and maximum; `None` before the first value
+    pub fn summary(&self) -> Option<(f32, f32, f32)> {
+        (self.count > 0).then_some((self.mean as f32, self.min, self.max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statistics_of_known_dataset() {
+        let stats = calculate_statistics(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]).unwrap();
+        assert_eq!(
+            stats,
+            Statistics {
+                average: 5.0,
+                min: 2.0,
+                max: 9.0,
+                median: 4.5,
+                std_dev: 2.0,
+            }
+        );
+    }
+
+    #[test]
+    fn median_of_odd_length_slice() {
+        let stats = calculate_statistics(&[9.0, 1.0, 3.0]).unwrap();
+        assert_eq!(stats.median, 3.0);
+    }
+
+    #[test]
+    fn statistics_of_single_element() {
+        let stats = calculate_statistics(&[21.5]).unwrap();
+        assert_eq!(
+            stats,
+            Statistics {
+                average: 21.5,
+                min: 21.5,
+                max: 21.5,
+                median: 21.5,
+                std_dev: 0.0,
+            }
+        );
+    }
+
+    #[test]
+    fn statistics_of_empty_slice() {
+        assert_eq!(calculate_statistics(&[]), None);
+    }
+
+    #[test]
+    fn statistics_skip_nan() {
+        let stats = calculate_statistics(&[9.0, f32::NAN, 1.0, 3.0]).unwrap();
+        assert_eq!((stats.average, stats.median), (13.0 / 3.0, 3.0));
+        assert_eq!(calculate_statistics(&[f32::NAN]), None);
+    }
+
+    #[test]
+    fn running_stats_skip_nan() {
+        let mut stats = RunningStats::default();
+        assert_eq!(stats.summary(), None);
+        for value in [21.0, f32::NAN, 25.0, 20.0] {
+            stats.push(value);
+        }
+        assert_eq!(stats.summary(), Some((22.0, 20.0, 25.0)));
+    }
+
+    #[test]
+    fn running_mean_stays_accurate_over_long_runs() {
+        let mut stats = RunningStats::default();
+        for _ in 0..10_000_000 {
+            stats.push(0.1);
+        }
+        assert_eq!(stats.summary(), Some((0.1, 0.1, 0.1)));
+    }
+}
@@ -0,0 +1,159 @@
+use crate::config::{NanRepresentation, StatsOutputConfig};
+use crate::logger::{format_value, FileLogger};
+use crate::sensor::Reading;
+use crate::stats::calculate_statistics;
+use std::io;
+use std::time::{Duration, Instant};
+
+const HEADER: &str = "timestamp, run_id, \
+    temperature_mean, temperature_min, temperature_max, temperature_std, \
+    humidity_mean, humidity_min, humidity_max, humidity_std, \
+    light_mean, light_min, light_max, light_std";
+
+// Periodic statistics written to their own CSV file, one row per interval
+// covering the readings taken during that interval
+pub struct StatsOutput {
+    logger: FileLogger,
+    run_id: String,
+    interval: Duration,
+    last_row: Instant,
+    temperature: Vec<f32>,
+    humidity: Vec<f32>,
+    light: Vec<f32>,
+}
+
+impl StatsOutput {
+    pub fn new(config: &StatsOutputConfig, nan: NanRepresentation, run_id: &str) -> Self {
+        let mut logger = FileLogger::new(&config.path);
+        logger.nan_representation = nan;
+        StatsOutput {
+            logger,
+            run_id: run_id.to_string(),
+            interval: Duration::from_secs(config.interval_secs),
+            last_row: Instant::now(),
+            temperature: Vec::new(),
+            humidity: Vec::new(),
+            light: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, reading: &Reading) -> io::Result<()> {
+        self.temperature.push(reading.temperature);
+        self.humidity.push(reading.humidity);
+        self.light.push(reading.light);
+        if self.last_row.elapsed() >= self.interval {
+            self.write_row()?;
+        }
+        Ok(())
+    }
+
+    // Write a row for the readings of the unfinished interval, so a run
+    // shorter than the interval still gets one
+    pub fn finish(mut self) -> io::Result<()> {
+        if !self.temperature.is_empty() {
+            self.write_row()?;
+        }
+        self.logger.finish()
+    }
+
+    fn write_row(&mut self) -> io::Result<()> {
+        self.logger.write_header(HEADER)?;
+        let mut row = vec![self.run_id.clone()];
+        for values in [&self.temperature, &self.humidity, &self.light] {
+            let stats = calculate_statistics(values).map_or([f32::NAN; 4], |stats| {
+                [stats.average, stats.min, stats.max, stats.std_dev]
+            });
+            for value in stats {
+                row.push(format_value(value, 2, self.logger.nan_representation));
+            }
+        }
+        self.logger.log(&row.join(", "))?;
+
+        self.last_row = Instant::now();
+        self.temperature.clear();
+        self.humidity.clear();
+        self.light.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn a_run_shorter_than_the_interval_gets_a_row() {
+        let path = std::env::temp_dir().join(format!("stats_output_{}.csv", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let _ = fs::remove_file(&path);
+        let config = StatsOutputConfig {
+            path: path.clone(),
+            interval_secs: 60,
+        };
+        let mut output = StatsOutput::new(&config, NanRepresentation::default(), "run-1");
+        for temperature in [20.0, 22.0] {
+            output
+                .record(&Reading {
+                    temperature,
+                    humidity: 40.0,
+                    light: 50.0,
+                    pressure: None,
+                    co2: None,
+                })
+                .unwrap();
+        }
+        output.finish().unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", contents);
+        assert_eq!(lines[0], HEADER);
+        assert!(
+            lines[1].ends_with(
+                ", run-1, 21.00, 20.00, 22.00, 1.00, 40.00, 40.00, 40.00, 0.00, \
+                 50.00, 50.00, 50.00, 0.00"
+            ),
+            "{}",
+            lines[1]
+        );
+    }
+
+    #[test]
+    fn dropouts_are_left_out_of_a_row() {
+        let path = std::env::temp_dir().join(format!("stats_dropout_{}.csv", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let _ = fs::remove_file(&path);
+        let config = StatsOutputConfig {
+            path: path.clone(),
+            interval_secs: 60,
+        };
+        let mut output = StatsOutput::new(&config, NanRepresentation::default(), "run-1");
+        // A dropped-out read reaches the statistics as NaN
+        for temperature in [20.0, f32::NAN, 22.0] {
+            output
+                .record(&Reading {
+                    temperature,
+                    humidity: 40.0,
+                    light: f32::NAN,
+                    pressure: None,
+                    co2: None,
+                })
+                .unwrap();
+        }
+        output.finish().unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let row = contents.lines().nth(1).unwrap();
+        assert!(
+            row.ends_with(
+                ", run-1, 21.00, 20.00, 22.00, 1.00, 40.00, 40.00, 40.00, 0.00, \
+                 NaN, NaN, NaN, NaN"
+            ),
+            "{}",
+            row
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use crate::alerts::Alert;
+use crate::bus::Channel;
+use crate::config::StatsdConfig;
+use crate::sensor::Reading;
+use std::collections::BTreeMap;
+use std::io;
+use std::net::UdpSocket;
+
+// Sends each reading as gauges and each alert as a counter to a StatsD
+// endpoint over UDP. Sends never block the run, and a metric that can't be
+// sent is dropped.
+pub struct StatsdSink {
+    socket: UdpSocket,
+    prefix: String,
+    // DogStatsD tags appended to every metric, e.g. `|#run_id:...`
+    tags: String,
+}
+
+impl StatsdSink {
+    pub fn connect(
+        config: &StatsdConfig,
+        run_id: &str,
+        run_tags: &BTreeMap<String, String>,
+    ) -> io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.connect((config.host.as_str(), config.port))?;
+        socket.set_nonblocking(true)?;
+        let tags = if config.dogstatsd_tags {
+            let tags: Vec<String> = std::iter::once(format!("run_id:{}", run_id))
+                .chain(
+                    run_tags
+                        .iter()
+                        .map(|(key, value)| format!("{}:{}", key, value)),
+                )
+                .collect();
+            format!("|#{}", tags.join(","))
+        } else {
+            String::new()
+        };
+        Ok(StatsdSink {
+            socket,
+            prefix: config.prefix.clone(),
+            tags,
+        })
+    }
+
+    // Gauges of every configured sensor in one packet; NaN readings are left out
+    pub fn send_reading(&self, reading: &Reading) {
+        let metrics: Vec<String> = [
+            ("temperature", Some(reading.temperature)),
+            ("humidity", Some(reading.humidity)),
+            ("light", Some(reading.light)),
+            ("pressure", reading.pressure),
+            ("co2", reading.co2),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value.filter(|value| value.is_finite())?)))
+        .map(|(name, value)| format!("{}.{}:{:.2}|g{}", self.prefix, name, value, self.tags))
+        .collect();
+        if !metrics.is_empty() {
+            self.send(&metrics.join("\n"));
+        }
+    }
+
+    // Counts an alert as e.g. `simulator.alerts.temperature.high`
+    pub fn count_alert(&self, alert: &Alert) {
+        let sensor = match alert.sensor {
+            Channel::Temperature => "temperature",
+            Channel::Humidity => "humidity",
+            Channel::Light => "light",
+            Channel::Pressure => "pressure",
+            Channel::Co2 => "co2",
+        };
+        self.send(&format!(
+            "{}.alerts.{}.{}:1|c{}",
+            self.prefix, sensor, alert.kind, self.tags
+        ));
+    }
+
+    fn send(&self, packet: &str) {
+        // Fire and forget: nobody may be listening, and the run goes on
+        let _ = self.socket.send(packet.as_bytes());
+    }
+}
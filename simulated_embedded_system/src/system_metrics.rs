@@ -0,0 +1,49 @@
+use crate::config::SystemMetric;
+use std::fs;
+
+// Current value of a host metric, or `None` where the host doesn't expose it
+pub fn read(metric: SystemMetric) -> Option<f32> {
+    match metric {
+        SystemMetric::CpuTemperature => cpu_temperature(),
+        SystemMetric::LoadAverage => load_average(),
+        SystemMetric::FreeMemory => free_memory(),
+    }
+}
+
+// °C of the first thermal zone that can be read
+fn cpu_temperature() -> Option<f32> {
+    let zones = fs::read_dir("/sys/class/thermal").ok()?;
+    zones
+        .flatten()
+        .filter(|zone| {
+            zone.file_name()
+                .to_string_lossy()
+                .starts_with("thermal_zone")
+        })
+        .find_map(|zone| {
+            let millidegrees: f32 = fs::read_to_string(zone.path().join("temp"))
+                .ok()?
+                .trim()
+                .parse()
+                .ok()?;
+            Some(millidegrees / 1000.0)
+        })
+}
+
+// One-minute load average
+fn load_average() -> Option<f32> {
+    let loadavg = fs::read_to_string("/proc/loadavg").ok()?;
+    loadavg.split_whitespace().next()?.parse().ok()
+}
+
+// Available memory as a percentage of the total
+fn free_memory() -> Option<f32> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let field = |name: &str| -> Option<f32> {
+        let line = meminfo.lines().find(|line| line.starts_with(name))?;
+        line.split_whitespace().nth(1)?.parse().ok()
+    };
+    let total = field("MemTotal:")?;
+    let available = field("MemAvailable:")?;
+    Some(available / total * 100.0)
+}
@@ -0,0 +1,172 @@
+use crate::config::UploadConfig;
+use chrono::Utc;
+use ring::{digest, hmac};
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// Uploads closed log segments to an S3-compatible bucket on background
+// threads. Segments that still fail after the retries are listed in the
+// pending file and retried when the next run starts.
+pub struct Uploader {
+    config: UploadConfig,
+    uploads: Vec<JoinHandle<()>>,
+}
+
+impl Uploader {
+    pub fn new(config: &UploadConfig) -> Self {
+        let mut uploader = Uploader {
+            config: config.clone(),
+            uploads: Vec::new(),
+        };
+        let pending = fs::read_to_string(&config.pending_file).unwrap_or_default();
+        if !pending.is_empty() {
+            let _ = fs::remove_file(&config.pending_file);
+            for path in pending.lines().filter(|line| !line.is_empty()) {
+                uploader.upload(path);
+            }
+        }
+        uploader
+    }
+
+    // Start uploading a closed segment
+    pub fn upload(&mut self, path: &str) {
+        let config = self.config.clone();
+        let path = path.to_string();
+        self.uploads.push(thread::spawn(move || {
+            upload_with_retry(&config, &path);
+        }));
+    }
+
+    // Wait for the uploads still in progress
+    pub fn finish(self) {
+        let running = self.uploads.iter().filter(|u| !u.is_finished()).count();
+        if running > 0 {
+            println!("[Upload]: Waiting for {} upload(s) to finish...", running);
+        }
+        for upload in self.uploads {
+            let _ = upload.join();
+        }
+    }
+}
+
+fn upload_with_retry(config: &UploadConfig, path: &str) {
+    let mut delay = Duration::from_secs(1);
+    for attempt in 1..=config.retries + 1 {
+        match put_object(config, path) {
+            Ok(key) => {
+                println!(
+                    "[Upload]: {} uploaded to s3://{}/{}",
+                    path, config.bucket, key
+                );
+                return;
+            }
+            Err(e) if attempt <= config.retries => {
+                println!(
+                    "[WARNING]: Upload of {} failed ({}), retrying in {}s",
+                    path,
+                    e,
+                    delay.as_secs()
+                );
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            Err(e) => {
+                println!(
+                    "[WARNING]: Upload of {} failed ({}); listed in {} for the next run",
+                    path, e, config.pending_file
+                );
+                let listed = OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(&config.pending_file)
+                    .and_then(|mut file| writeln!(file, "{}", path));
+                if let Err(e) = listed {
+                    println!("[WARNING]: Could not record pending upload: {}", e);
+                }
+            }
+        }
+    }
+}
+
+// Upload a file with a SigV4-signed PUT, returning its object key
+fn put_object(config: &UploadConfig, path: &str) -> Result<String, Box<dyn Error>> {
+    let body = fs::read(path)?;
+    let file_name = Path::new(path)
+        .file_name()
+        .ok_or("segment path has no file name")?
+        .to_string_lossy();
+    let key = format!("{}{}", config.prefix, file_name);
+
+    let (access_key_id, secret_access_key) = config.credentials()?;
+    let endpoint = config.endpoint.trim_end_matches('/');
+    let host = endpoint
+        .split_once("://")
+        .map_or(endpoint, |(_, rest)| rest)
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    let uri = format!("/{}/{}", config.bucket, uri_encode(&key));
+
+    let now = Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let payload_hash = hex(digest::digest(&digest::SHA256, &body).as_ref());
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical_request = format!(
+        "PUT\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        uri, host, payload_hash, amz_date, signed_headers, payload_hash
+    );
+    let scope = format!("{}/{}/s3/aws4_request", date, config.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(digest::digest(&digest::SHA256, canonical_request.as_bytes()).as_ref())
+    );
+
+    let mut signing_key = format!("AWS4{}", secret_access_key).into_bytes();
+    for part in [date.as_str(), config.region.as_str(), "s3", "aws4_request"] {
+        signing_key = hmac_sha256(&signing_key, part.as_bytes());
+    }
+    let signature = hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        access_key_id, scope, signed_headers, signature
+    );
+
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(60))
+        .build();
+    agent
+        .put(&format!("{}{}", endpoint, uri))
+        .set("x-amz-date", &amz_date)
+        .set("x-amz-content-sha256", &payload_hash)
+        .set("Authorization", &authorization)
+        .send_bytes(&body)?;
+    Ok(key)
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    hmac::sign(&key, data).as_ref().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Percent-encode an object key as SigV4 expects, keeping `/` separators
+fn uri_encode(key: &str) -> String {
+    key.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
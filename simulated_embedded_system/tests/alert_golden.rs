@@ -0,0 +1,77 @@
+// Replays each fixture log in tests/fixtures/alerts through the alert engine
+// and compares the alerts against the fixture's golden file.
+//
+// Each fixture is a `<name>.log` recorded log with a `<name>.toml` holding the
+// `[alerts]` settings to replay it with; the expected alerts are in
+// `<name>.alerts`. After an intentional change to the alert logic, regenerate
+// the golden files with
+//
+//     UPDATE_GOLDEN=1 cargo test --test alert_golden
+//
+// and review the diff before committing it.
+
+use simulated_embedded_system::config::{AlertConfig, NanRepresentation, TemperatureUnit};
+use simulated_embedded_system::replay::replay_alerts;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/alerts");
+
+// Alerts raised by replaying the fixture log at `log_path`, one per line
+fn replay_fixture(log_path: &Path) -> String {
+    let config_path = log_path.with_extension("toml");
+    let alerts = AlertConfig::load(&config_path.to_string_lossy(), TemperatureUnit::Celsius)
+        .unwrap_or_else(|e| panic!("{}: {}", config_path.display(), e));
+    let logs = fs::read_to_string(log_path).unwrap();
+    replay_alerts(&logs, &alerts, NanRepresentation::default())
+        .iter()
+        .map(|(timestamp, alert)| format!("{}, {:?}, {}\n", timestamp, alert.sensor, alert.message))
+        .collect()
+}
+
+fn fixture_logs() -> Vec<PathBuf> {
+    let mut logs: Vec<PathBuf> = fs::read_dir(FIXTURES)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "log"))
+        .collect();
+    logs.sort();
+    logs
+}
+
+#[test]
+fn alerts_match_golden_files() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let logs = fixture_logs();
+    assert!(!logs.is_empty(), "no fixture logs in {}", FIXTURES);
+
+    let mut mismatches = Vec::new();
+    for log_path in logs {
+        let golden_path = log_path.with_extension("alerts");
+        let actual = replay_fixture(&log_path);
+        if update {
+            fs::write(&golden_path, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&golden_path).unwrap_or_else(|e| {
+            panic!(
+                "{}: {} (run with UPDATE_GOLDEN=1 to create it)",
+                golden_path.display(),
+                e
+            )
+        });
+        if actual != expected {
+            mismatches.push(format!(
+                "{}:\n--- expected\n{}--- actual\n{}",
+                golden_path.display(),
+                expected,
+                actual
+            ));
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "alerts differ from the golden files (run with UPDATE_GOLDEN=1 if the change is intended):\n{}",
+        mismatches.join("\n")
+    );
+}
@@ -0,0 +1,41 @@
+// Setup shared by the tests that run a short simulation and check its log
+
+use simulated_embedded_system::config::Config;
+use simulated_embedded_system::simulation::{run_simulation, RunOptions};
+use std::collections::BTreeMap;
+use std::fs;
+
+// Run a one-second simulation with the given `[sensors]` settings, logging to
+// a temporary file named after `name`, and return its config and log
+pub fn run_for_one_second(name: &str, sensors: &str) -> (Config, String) {
+    let log_path = std::env::temp_dir().join(format!("{}_{}.txt", name, std::process::id()));
+    let log_path = log_path.to_string_lossy().into_owned();
+    let _ = fs::remove_file(&log_path);
+    let config = Config::parse(&format!(
+        "[sensors]
+         {}
+         [storage]
+         log_file_path = {:?}
+         [display]
+         real_time_graph = false
+         [alerts]
+         temperature_threshold = 30.0
+         humidity_threshold = 60.0
+         light_threshold = 80.0",
+        sensors, log_path
+    ))
+    .unwrap();
+    let options = RunOptions {
+        duration_seconds: 1,
+        interactive: false,
+        force: true,
+        scenario: None,
+        tags: BTreeMap::new(),
+        replay_file: None,
+    };
+    run_simulation(&config, None, None, &options).unwrap();
+
+    let logs = fs::read_to_string(&log_path).unwrap();
+    fs::remove_file(&log_path).unwrap();
+    (config, logs)
+}
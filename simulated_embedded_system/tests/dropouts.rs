@@ -0,0 +1,40 @@
+// Runs a short simulation in which every sensor read drops out and checks
+// that the dropouts are logged in place of the readings.
+
+mod common;
+
+use simulated_embedded_system::logger::{is_dropout_line, parse_log_line};
+
+#[test]
+fn dropouts_are_logged_instead_of_readings() {
+    let (config, logs) = common::run_for_one_second(
+        "dropouts",
+        "temperature_sampling_rate_ms = 100
+         humidity_sampling_rate_ms = 100
+         light_sampling_rate_ms = 100
+         tick_interval_ms = 100
+         failure_rate = 1.0
+         seed = 1",
+    );
+    for sensor in ["Temperature", "Humidity", "Light"] {
+        let message = format!("{} read failed", sensor);
+        assert!(
+            logs.lines()
+                .any(|line| is_dropout_line(line) && line.ends_with(&message)),
+            "no dropout of {} in:\n{}",
+            sensor,
+            logs
+        );
+    }
+    // The rows carry no values while every read drops out
+    let readings: Vec<_> = logs
+        .lines()
+        .filter_map(|line| parse_log_line(line, config.storage.nan_representation))
+        .collect();
+    assert!(!readings.is_empty());
+    for (_, reading) in readings {
+        assert!(reading.temperature.is_nan());
+        assert!(reading.humidity.is_nan());
+        assert!(reading.light.is_nan());
+    }
+}
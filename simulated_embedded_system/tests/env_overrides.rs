@@ -0,0 +1,84 @@
+// Loads the example config with `ESS_*` environment variables set and checks
+// that they take precedence over the file. Environment variables are shared by
+// the whole test process, so this lives in its own test binary and sets them
+// in a single test.
+
+use simulated_embedded_system::bus::Channel;
+use simulated_embedded_system::config::{config_error_exit_code, Config};
+use std::env;
+use std::fs;
+use std::time::Duration;
+
+const EXAMPLE_CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/config.toml");
+
+#[test]
+fn environment_variables_override_the_config_file() {
+    env::set_var("ESS_TEMPERATURE_SAMPLING_RATE_MS", "250");
+    env::set_var("ESS_LOG_FILE_PATH", "override.txt");
+    env::set_var("ESS_HUMIDITY_THRESHOLD", "55.5");
+    let config = Config::load(EXAMPLE_CONFIG).unwrap();
+    assert_eq!(
+        config.sensors.sampling_rate(Channel::Temperature),
+        Some(Duration::from_millis(250))
+    );
+    assert_eq!(config.storage.log_file_path, "override.txt");
+    assert_eq!(config.alerts.humidity_threshold, 55.5);
+
+    // Overridden values are validated like the file's
+    env::set_var("ESS_HUMIDITY_THRESHOLD", "550");
+    assert_eq!(
+        Config::load(EXAMPLE_CONFIG).unwrap_err().to_string(),
+        "humidity_threshold must be between 0 and 100, got 550"
+    );
+
+    env::set_var("ESS_HUMIDITY_THRESHOLD", "high");
+    assert_eq!(
+        Config::load(EXAMPLE_CONFIG).unwrap_err().to_string(),
+        "invalid ESS_HUMIDITY_THRESHOLD=\"high\": invalid float literal"
+    );
+    env::remove_var("ESS_HUMIDITY_THRESHOLD");
+
+    env::set_var("ESS_TEMPERATURE_SAMPLING_RATE", "2");
+    env::remove_var("ESS_TEMPERATURE_SAMPLING_RATE_MS");
+    env::set_var("ESS_HUMIDITY_THRESHOLD", "55.5");
+
+    // Saving the settings keeps the file's values of overridden keys, unless
+    // they were changed after loading
+    let path = env::temp_dir().join(format!("env_overrides_{}.toml", std::process::id()));
+    let path = path.to_string_lossy().into_owned();
+    fs::copy(EXAMPLE_CONFIG, &path).unwrap();
+    let mut config = Config::load(&path).unwrap();
+    assert_eq!(config.sensors.temperature_sampling_rate_ms, Some(2000));
+    config.alerts.humidity_threshold = 60.0;
+    config.save(&path).unwrap();
+    let saved = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(
+        saved.contains("temperature_sampling_rate = 1\n"),
+        "{}",
+        saved
+    );
+    assert!(!saved.contains("temperature_sampling_rate_ms"), "{}", saved);
+    assert!(
+        saved.contains("log_file_path = \"sensor_logs.txt\"\n"),
+        "{}",
+        saved
+    );
+    assert!(saved.contains("humidity_threshold = 60.0\n"), "{}", saved);
+    env::remove_var("ESS_HUMIDITY_THRESHOLD");
+    env::remove_var("ESS_LOG_FILE_PATH");
+
+    env::set_var("ESS_TEMPERATURE_SAMPLING_RATE_MS", "250");
+    assert_eq!(
+        Config::load(EXAMPLE_CONFIG).unwrap_err().to_string(),
+        "set only one of ESS_TEMPERATURE_SAMPLING_RATE and ESS_TEMPERATURE_SAMPLING_RATE_MS"
+    );
+
+    // The config file can't set the exit code for failing to load it
+    assert_eq!(config_error_exit_code(), 4);
+    env::set_var("ESS_EXIT_CODE_CONFIG_ERROR", "10");
+    assert_eq!(config_error_exit_code(), 10);
+    env::set_var("ESS_EXIT_CODE_CONFIG_ERROR", "0");
+    assert_eq!(config_error_exit_code(), 4);
+    env::remove_var("ESS_EXIT_CODE_CONFIG_ERROR");
+}
@@ -0,0 +1,30 @@
+// Runs a short simulation with sensors on different sampling rates and checks
+// that a row is logged whenever any of them was read, not only when they all
+// happen to be read on the same tick.
+
+mod common;
+
+use simulated_embedded_system::logger::parse_log_line;
+
+#[test]
+fn a_row_is_logged_whenever_any_sensor_is_read() {
+    let (config, logs) = common::run_for_one_second(
+        "mismatched",
+        "temperature_sampling_rate_ms = 200
+         humidity_sampling_rate_ms = 300
+         light_sampling_rate_ms = 600
+         tick_interval_ms = 100
+         seed = 1",
+    );
+    let readings: Vec<_> = logs
+        .lines()
+        .filter_map(|line| parse_log_line(line, config.storage.nan_representation))
+        .collect();
+    // Ticks 1 to 9 fall within the second. Temperature is read on ticks 2, 4,
+    // 6 and 8 and humidity on 3, 6 and 9; light only on 6, where the others
+    // are read too. All three are read together on tick 6 alone.
+    assert_eq!(readings.len(), 6, "unexpected rows in:\n{}", logs);
+    // Until its first read on tick 6, light is logged as NaN
+    assert!(readings[0].1.light.is_nan());
+    assert!(!readings[3].1.light.is_nan());
+}